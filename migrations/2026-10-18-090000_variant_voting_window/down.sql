@@ -0,0 +1,2 @@
+ALTER TABLE poll_variant
+    DROP COLUMN is_finished;
@@ -0,0 +1,2 @@
+ALTER TABLE poll_variant
+    ADD COLUMN is_finished bool not null default false;
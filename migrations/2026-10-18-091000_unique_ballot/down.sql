@@ -0,0 +1 @@
+DROP INDEX votes_results_ballot;
@@ -0,0 +1,10 @@
+-- keep the latest score when a ballot was stored more than once
+DELETE
+FROM votes_results a
+    USING votes_results b
+WHERE a.id < b.id
+  AND a.user_id = b.user_id
+  AND a.poll_variant_id = b.poll_variant_id
+  AND a.dialog_variant_id = b.dialog_variant_id;
+
+create unique index votes_results_ballot on votes_results (user_id, poll_variant_id, dialog_variant_id);
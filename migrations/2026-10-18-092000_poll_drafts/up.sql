@@ -0,0 +1,10 @@
+create table poll_drafts
+(
+    id            SERIAL PRIMARY KEY,
+    user_slack_id TEXT      not null,
+    view_id       TEXT      not null,
+    draft         TEXT      not null,
+    updated_at    timestamp not null default now()
+);
+
+create unique index poll_drafts_author_view on poll_drafts (user_slack_id, view_id);
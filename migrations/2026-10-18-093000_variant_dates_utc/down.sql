@@ -0,0 +1,3 @@
+UPDATE poll_variant
+SET start_date = (start_date AT TIME ZONE 'UTC') AT TIME ZONE current_setting('TimeZone'),
+    end_date   = (end_date AT TIME ZONE 'UTC') AT TIME ZONE current_setting('TimeZone');
@@ -0,0 +1,5 @@
+-- Variant windows used to be written in the server local time, from now on they are kept in UTC.
+-- The server local time is assumed to be the database session timezone.
+UPDATE poll_variant
+SET start_date = (start_date AT TIME ZONE current_setting('TimeZone')) AT TIME ZONE 'UTC',
+    end_date   = (end_date AT TIME ZONE current_setting('TimeZone')) AT TIME ZONE 'UTC';
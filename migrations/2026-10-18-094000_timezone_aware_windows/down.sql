@@ -0,0 +1,7 @@
+ALTER TABLE channel_users
+    DROP COLUMN tz_offset,
+    DROP COLUMN tz;
+
+ALTER TABLE poll_variant
+    ALTER COLUMN start_date TYPE timestamp USING start_date AT TIME ZONE 'UTC',
+    ALTER COLUMN end_date TYPE timestamp USING end_date AT TIME ZONE 'UTC';
@@ -0,0 +1,9 @@
+-- Variant windows are already written in UTC, the column type now says so.
+ALTER TABLE poll_variant
+    ALTER COLUMN start_date TYPE timestamptz USING start_date AT TIME ZONE 'UTC',
+    ALTER COLUMN end_date TYPE timestamptz USING end_date AT TIME ZONE 'UTC';
+
+-- Timezone of the user from Slack users.info, tz_offset is in seconds east of UTC.
+ALTER TABLE channel_users
+    ADD COLUMN tz TEXT,
+    ADD COLUMN tz_offset INTEGER NOT NULL DEFAULT 0;
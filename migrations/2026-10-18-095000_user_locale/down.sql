@@ -0,0 +1 @@
+ALTER TABLE channel_users DROP COLUMN locale;
@@ -0,0 +1,2 @@
+-- Slack locale of the user, e.g. en-US
+ALTER TABLE channel_users ADD COLUMN locale TEXT;
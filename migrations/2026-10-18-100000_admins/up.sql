@@ -0,0 +1,11 @@
+-- Organizers allowed to create, post and close polls.
+-- A NULL channel grants the role in every channel.
+create table admins
+(
+    id            SERIAL PRIMARY KEY,
+    user_slack_id TEXT not null,
+    channel       TEXT
+);
+
+create unique index admins_global on admins (user_slack_id) where channel is null;
+create unique index admins_channel on admins (user_slack_id, channel) where channel is not null;
@@ -0,0 +1,9 @@
+alter table poll add column time TEXT;
+
+update poll
+set time = poll_messages.ts
+from poll_messages
+where poll_messages.poll_id = poll.id
+  and poll_messages.page = 0;
+
+drop table poll_messages;
@@ -0,0 +1,16 @@
+-- Large polls are posted as several messages, page 0 holds the header.
+create table poll_messages
+(
+    id      SERIAL PRIMARY KEY,
+    poll_id INTEGER not null references poll (id) on delete cascade,
+    page    INTEGER not null,
+    ts      TEXT    not null,
+    unique (poll_id, page)
+);
+
+insert into poll_messages (poll_id, page, ts)
+select id, 0, time
+from poll
+where time is not null;
+
+alter table poll drop column time;
@@ -0,0 +1,3 @@
+ALTER TABLE channel_users
+    DROP COLUMN display_name,
+    DROP COLUMN real_name;
@@ -0,0 +1,4 @@
+-- Names from the Slack profile, shown next to the avatars of voters.
+ALTER TABLE channel_users
+    ADD COLUMN display_name TEXT,
+    ADD COLUMN real_name TEXT;
@@ -0,0 +1 @@
+ALTER TABLE poll DROP COLUMN visibility;
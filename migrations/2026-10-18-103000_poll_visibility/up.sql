@@ -0,0 +1,2 @@
+-- What the channel sees before the poll is closed: public, counts_only or hidden_until_close
+ALTER TABLE poll ADD COLUMN visibility TEXT NOT NULL DEFAULT 'public';
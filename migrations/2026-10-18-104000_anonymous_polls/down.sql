@@ -0,0 +1,5 @@
+DELETE FROM votes_results WHERE user_id IS NULL;
+ALTER TABLE votes_results DROP COLUMN ballot;
+ALTER TABLE votes_results ALTER COLUMN user_id SET NOT NULL;
+DROP TABLE anonymous_voters;
+ALTER TABLE poll DROP COLUMN is_anonymous;
@@ -0,0 +1,15 @@
+ALTER TABLE poll ADD COLUMN is_anonymous BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Keyed hash of (poll, user) per voted variant of an anonymous poll, it only prevents repeated ballots.
+-- No serial id, an insertion order would match the voters to the ballots.
+CREATE TABLE anonymous_voters
+(
+    poll_id         INTEGER NOT NULL REFERENCES poll (id) ON DELETE CASCADE,
+    poll_variant_id INTEGER NOT NULL REFERENCES poll_variant (id) ON DELETE CASCADE,
+    voter_hash      TEXT    NOT NULL,
+    PRIMARY KEY (poll_variant_id, voter_hash)
+);
+
+-- Scores of anonymous polls have no user, the scores of one ballot share a random number instead
+ALTER TABLE votes_results ALTER COLUMN user_id DROP NOT NULL;
+ALTER TABLE votes_results ADD COLUMN ballot BIGINT;
@@ -0,0 +1 @@
+ALTER TABLE dialog_variants DROP COLUMN weight;
@@ -0,0 +1,2 @@
+-- Relative weight of the criterion in the weighted report, equal weights keep the old totals
+ALTER TABLE dialog_variants ADD COLUMN weight INTEGER NOT NULL DEFAULT 1 CHECK (weight > 0);
@@ -0,0 +1,2 @@
+ALTER TABLE dialog_variants DROP COLUMN track;
+DROP TABLE poll_jury;
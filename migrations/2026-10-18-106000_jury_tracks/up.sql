@@ -0,0 +1,11 @@
+-- Jury of the poll, every other voter belongs to the audience
+CREATE TABLE poll_jury
+(
+    id            SERIAL PRIMARY KEY,
+    poll_id       INTEGER NOT NULL REFERENCES poll (id) ON DELETE CASCADE,
+    user_slack_id TEXT    NOT NULL,
+    UNIQUE (poll_id, user_slack_id)
+);
+
+-- Voter group scoring the criterion, jury or audience
+ALTER TABLE dialog_variants ADD COLUMN track TEXT NOT NULL DEFAULT 'audience';
@@ -1,28 +1,51 @@
 use crate::actions_response::{ActionResponse, BlockAction};
+use crate::commands::AdminCommand;
+use crate::config::Config;
 use crate::data::{
-    create_connection, Database, FindUser, GetPollReport, ReadDialogVariantsForLastDay,
-    ReadLastPoll, ReadPollVariant, ReadVotesForCurrentUser, SingleVariantSource, UpdatePollTime,
-    WriteNewPoll, WriteUser, WriteVotes,
+    create_connection, voter_hash, Admin, AdminScope, AdminWrite, BallotWrite, ChannelUser,
+    ChannelUserWrite, CheckAdmin, ClosePoll, Database, FindUser, GetPollReport, GrantAdmin,
+    HasAnonymousBallot, ListAdmins, ListPolls, PollSummary, ReadHome, ReadPoll, ReadPollDraft,
+    ReadPollVariant, ReadVoterCriteria, ReadVotesForCurrentUser, RevokeAdmin, SaveDraftChannel,
+    SaveDraftDialogVariants, SavePollMessages, SingleVariantSource, TakePollDraft, VotesResult,
+    WithdrawVotes, WriteBallot, WriteNewPoll, WriteUser,
 };
+use crate::error::{AppError, Requester};
+use crate::locale::{fill, workspace_messages, Locale, Messages};
 use crate::poll_state::PollData;
+use crate::scheduler::VotingScheduler;
 use crate::slack_ui::{
-    create_poll_menu, create_poll_report_view, create_poll_view, show_answered_request_view,
-    show_not_ready_request_view, update_message_response,
+    create_criterion_inputs, create_home_view, create_poll_menu, create_poll_report_view,
+    create_poll_view, create_variant_inputs, create_vote_view, create_voters_view,
+    publish_home_view, score_input_id, show_already_voted_message,
+    show_anonymous_voted_request_view, show_closed_request_view, show_ephemeral_message,
+    show_error_message, show_finished_request_view, show_not_ready_request_view, slack_date,
+    update_message_response, BALLOT_ANONYMOUS, CRITERION_INPUTS, POLL_BALLOT_INPUT,
+    POLL_JURY_INPUT, POLL_SETTING_INPUTS, POLL_VISIBILITY_INPUT, VARIANT_INPUTS,
+    WITHDRAW_VOTE_INPUT, WITHDRAW_VOTE_YES,
 };
-use crate::ui_poll_view::{DialogView, DialogViewVariant, SingleVariant};
-use crate::DIALOG_VARIANT_CREATE_ID;
-use actix::Addr;
+use crate::timezone::{now_utc, UserTimeZone};
+use crate::ui_poll_view::{
+    DialogViewVariant, PollView, PollVisibility, SingleVariant, Track, VariantKey,
+    EDIT_VOTE_ACTION_PREFIX, WHO_VOTED_ACTION_PREFIX,
+};
+use crate::validation::{
+    check, check_jury_tracks, parse_max_score, parse_picked_date, parse_picked_time, parse_text,
+    parse_weight, parse_window_end, ValidationErrors, MIN_SCORE,
+};
+use crate::{CHANNEL_CHOOSE, DIALOG_VARIANT_CREATE_ID};
+use actix::{Actor, Addr, Arbiter};
 use actix_web::web::Form;
 use actix_web::{Error, HttpRequest, HttpResponse, Responder};
 use chrono::{Local, NaiveDateTime};
-use futures::Future;
+use futures::future::Either;
+use futures::{Future, Stream};
 use serde_json::{Map, Value};
 use slacker::{
-    BlockElement, Dialog, DialogElement, DialogOpen, DialogOptionGroup, GetUserInfo, LayoutBlock,
-    MessageVisibility, PostMessage, PostMessageResponse, Slacker, UserInfoResponse, View, ViewOpen,
-    ViewPush, ViewUpdate,
+    BlockElement, GetUserInfo, LayoutBlock, MessageVisibility, PostMessage, PostMessageResponse,
+    Slacker, UserInfoResponse, View, ViewOpen, ViewPush, ViewUpdate,
 };
 use std::collections::HashMap;
+use std::convert::TryFrom;
 use std::intrinsics::transmute;
 use std::mem::swap;
 use std::str::FromStr;
@@ -30,351 +53,799 @@ use std::sync::atomic::AtomicPtr;
 use std::sync::{Arc, Mutex, MutexGuard};
 use std::time::Duration;
 
+/// Number of polls shown by `/poll list`
+const RECENT_POLLS: i64 = 20;
+
+/// Variant of the poll creation view, dates are in the timezone of the admin.
+struct PickedVariant {
+    title: String,
+    variant: String,
+    start_date: NaiveDateTime,
+    end_date: Option<NaiveDateTime>,
+}
+
 #[derive(Clone)]
 pub struct SlackApplication {
     data: Addr<Database>,
-    state: Arc<Mutex<Option<PollData>>>,
     slacker: Slacker,
-    pub user_admin: String,
+    /// Slack id of the owner from the configuration, an admin everywhere who cannot be revoked
+    owner: String,
+    /// Bot token for the api methods called without `Slacker`
+    api_key: String,
+    work_space: String,
+    /// Key of the voter hashes of anonymous polls
+    anonymous_key: Option<String>,
 }
 
 impl SlackApplication {
-    pub fn new() -> Self {
+    pub fn new(config: &Config) -> Self {
         SlackApplication {
-            data: create_connection(),
-            state: Arc::new(Mutex::new(Option::Some(PollData::default()))),
-            slacker: Slacker::new(
-                std::env::var("API_KEY").unwrap().as_str(),
-                std::env::var("WORK_SPACE").unwrap().as_str(),
-            ),
-            user_admin: std::env::var("USER_ADMIN").unwrap_or_default(),
+            data: create_connection(config),
+            slacker: Slacker::new(&config.slack.api_key, &config.slack.work_space),
+            owner: config.slack.user_admin.clone(),
+            api_key: config.slack.api_key.clone(),
+            work_space: config.slack.work_space.clone(),
+            anonymous_key: config.voting.anonymous_key.clone(),
         }
     }
 
-    pub fn process_dialog_submission(&self, block_action: BlockAction) {
+    pub fn start_voting_scheduler(&self) {
+        let data = self.data.clone();
+        let slacker = self.slacker.clone();
+        VotingScheduler::start_in_arbiter(&Arbiter::new(), move |_| VotingScheduler {
+            data,
+            slacker,
+        });
+    }
+
+    /// Runs `task` in background, its failure is reported to the requester.
+    fn spawn_for<F>(&self, requester: Requester, task: F)
+    where
+        F: Future<Item = (), Error = AppError> + 'static,
+    {
+        let client = self.slacker.clone();
+        actix::spawn(task.or_else(move |error| show_error_message(client, requester, error)));
+    }
+
+    /// Fails unless the user is the owner or has an admin role in `scope`.
+    fn ensure_admin(
+        &self,
+        user_id: String,
+        scope: AdminScope,
+    ) -> Box<dyn Future<Item = (), Error = AppError>> {
+        if !self.owner.is_empty() && user_id == self.owner {
+            return Box::new(futures::future::ok(()));
+        }
+        Box::new(
+            self.data
+                .send(CheckAdmin(user_id, scope))
+                .map_err(AppError::from)
+                .and_then(|is_admin| is_admin)
+                .and_then(|is_admin| {
+                    if is_admin {
+                        Ok(())
+                    } else {
+                        Err(AppError::State(workspace_messages().no_rights.to_owned()))
+                    }
+                }),
+        )
+    }
+
+    /// Reads the user from the database. The profile is loaded from Slack on the first
+    /// interaction of the user, or when their timezone or name is not cached yet.
+    fn find_or_create_user(
+        &self,
+        slack_user_id: String,
+    ) -> Box<dyn Future<Item = ChannelUser, Error = AppError>> {
+        let data = self.data.clone();
+        let slacker = self.slacker.clone();
+        Box::new(
+            self.data
+                .send(FindUser(slack_user_id.clone()))
+                .map_err(AppError::from)
+                .and_then(|user| user)
+                .then(move |found| match found {
+                    Ok(user) if user.tz.is_some() && user.real_name.is_some() => {
+                        Either::A(futures::future::ok(user))
+                    }
+                    _ => {
+                        println!("Get from slack api user");
+                        Either::B(
+                            slacker
+                                .get(GetUserInfo(slack_user_id))
+                                .map_err(AppError::slack)
+                                .and_then(ChannelUserWrite::try_from)
+                                .and_then(move |user| {
+                                    data.send(WriteUser(user))
+                                        .map_err(AppError::from)
+                                        .and_then(|user| user)
+                                }),
+                        )
+                    }
+                }),
+        )
+    }
+
+    /// Texts for the views opened for the user.
+    fn user_messages(
+        &self,
+        slack_user_id: String,
+    ) -> impl Future<Item = &'static Messages, Error = AppError> {
+        self.find_or_create_user(slack_user_id)
+            .map(|user| messages_of(&user))
+    }
+
+    fn user_time_zone(
+        &self,
+        slack_user_id: String,
+    ) -> impl Future<Item = UserTimeZone, Error = AppError> {
+        self.find_or_create_user(slack_user_id)
+            .map(|user| UserTimeZone::from(&user))
+    }
+
+    /// Saves the scores of a submitted scoring modal, or withdraws the ballot when the user
+    /// chose so while editing it.
+    pub fn process_vote_submission(&self, block_action: BlockAction) -> Result<(), AppError> {
         let slacker = self.clone().slacker;
-        let slacker_client = self.clone().slacker;
         let data = self.clone().data;
-        let old_view = self.clone().state;
-        let callback_id = i32::from_str(&block_action.callback_id.clone()).unwrap();
-        let user_id = block_action.user.id.clone();
-        let dialog_submission = data
-            .send(FindUser(block_action.user.id.clone()))
-            .map_err(|e| println!("Cannot find user {}", e))
-            .and_then(|result| {
-                println!("Get from data base");
-                result
-            })
-            .or_else(move |_| {
-                println!("Get from slack api user");
-                slacker_client
-                    .get(GetUserInfo(user_id))
-                    .map_err(|e| println!("Cannot load user info {}", e))
-                    .map(|user| user.into())
-            })
-            .and_then(move |result| {
-                let user_info = result;
-                let user_image = user_info.user_thumbnail.unwrap();
-                let user_id = user_info.user_slack_id.unwrap();
+        let requester = Requester::from(&block_action);
+        let view = block_action
+            .view
+            .ok_or_else(|| AppError::Parse("Vote submission without view".to_owned()))?;
+        let callback_id = view.callback_id.clone().unwrap_or_default();
+        let mut values = view
+            .state
+            .ok_or_else(|| AppError::Parse("Vote submission without state".to_owned()))?
+            .values;
+        let (key, submission) = ballot_submission(&callback_id, &mut values)?;
+        let slack_user_id = block_action.user.id.clone();
+        let anonymous_voter = self
+            .anonymous_key
+            .as_ref()
+            .map(|anonymous_key| voter_hash(anonymous_key, key.poll_id, &slack_user_id));
+        let vote_submission = self
+            .find_or_create_user(block_action.user.id.clone())
+            .and_then(move |user| {
+                let user_id = user.id;
+                let text = messages_of(&user);
                 let database = data.clone();
-                data.send(WriteUser(user_id.clone(), user_image.clone()))
-                    .map_err(|e| println!("Cannot write user {}", e))
-                    .and_then(move |user| {
-                        let user_id = user.unwrap().id;
-                        let answers = block_action.submission;
-                        data.send(ReadDialogVariantsForLastDay)
-                            .map(|variants| variants.unwrap())
-                            .map_err(|_| ())
+                let write = if submission == BallotSubmission::Withdraw {
+                    Either::A(
+                        data.send(WithdrawVotes(user_id, key.poll_id, key.variant_id))
+                            .map_err(AppError::from)
+                            .and_then(|result| result)
+                            .map(|_| {
+                                println!("Result withdrawn");
+                                BallotWrite::Written
+                            }),
+                    )
+                } else {
+                    Either::B(
+                        data.send(ReadVoterCriteria(key.poll_id, slack_user_id.clone()))
+                            .map_err(AppError::from)
+                            .and_then(|variants| variants)
                             .and_then(move |variants| {
-                                let mut futures = vec![];
-                                for variant in variants {
-                                    let write_vote = data
-                                        .send(WriteVotes(
-                                            user_id,
-                                            variant.day_id,
-                                            callback_id,
-                                            variant.id,
-                                            i32::from_str(&answers[&variant.variant_text]).unwrap(),
-                                        ))
-                                        .map_err(|_| ());
-                                    futures.push(write_vote)
-                                }
-                                futures::future::join_all(futures)
-                            })
-                            .map(|_| println!("Result written"))
-                            .and_then(move |_| {
-                                database
-                                    .send(ReadLastPoll)
-                                    .map_err(|_| println!("Cannot read poll"))
-                            })
-                            .and_then(move |result| {
-                                //todo change to data base poll time
-                                let result = result.unwrap();
-                                update_message_response(
-                                    slacker,
-                                    result.time.clone().unwrap(),
-                                    result,
-                                )
-                            })
-                    })
+                                let scores = variants
+                                    .iter()
+                                    .map(|variant| {
+                                        selected_value(&mut values, &score_input_id(variant.id))
+                                            .and_then(|score| i32::from_str(&score).ok())
+                                            .map(|score| (variant.id, score))
+                                    })
+                                    .collect::<Option<Vec<_>>>()
+                                    .ok_or_else(|| {
+                                        AppError::Parse(format!("Incomplete ballot {:?}", values))
+                                    });
+                                futures::future::result(scores).and_then(move |scores| {
+                                    data.send(WriteBallot {
+                                        user_id,
+                                        poll_id: key.poll_id,
+                                        variant_id: key.variant_id,
+                                        scores,
+                                        replace: submission == BallotSubmission::Edit,
+                                        voter_hash: anonymous_voter,
+                                    })
+                                    .map_err(AppError::from)
+                                    .and_then(|result| result)
+                                })
+                            }),
+                    )
+                };
+                write.and_then(move |written| match written {
+                    BallotWrite::AlreadyVoted => {
+                        println!("Repeated ballot from {}", slack_user_id);
+                        Either::A(show_already_voted_message(
+                            slacker,
+                            slack_user_id,
+                            text.already_voted,
+                        ))
+                    }
+                    BallotWrite::AnonymousAlreadyVoted => Either::A(show_already_voted_message(
+                        slacker,
+                        slack_user_id,
+                        text.anonymous_ballot_final,
+                    )),
+                    BallotWrite::Written => {
+                        println!("Result written");
+                        Either::B(
+                            database
+                                .send(ReadPoll(key.poll_id))
+                                .map_err(AppError::from)
+                                .and_then(|result| result)
+                                .and_then(move |result| {
+                                    update_message_response(
+                                        slacker,
+                                        result,
+                                        Some(vec![key.variant_id]),
+                                        workspace_messages(),
+                                    )
+                                }),
+                        )
+                    }
+                })
             });
-        actix::spawn(dialog_submission);
+        self.spawn_for(requester, vote_submission);
+        Ok(())
     }
 
-    pub fn process_poll_request(&self, trigger_id: String) {
-        let app_data = self.clone();
-        let task = app_data
-            .slacker
-            .post(create_poll_menu(trigger_id))
-            .map_err(|e| println!("Error while request poll {}", e))
+    pub fn process_poll_request(&self, trigger_id: String, requester: Requester) {
+        let slacker = self.slacker.clone();
+        let user_id = requester.user_id.clone();
+        let task = self
+            .ensure_admin(user_id.clone(), AdminScope::AnyChannel)
+            .join(self.user_messages(user_id))
+            .and_then(move |(_, text)| {
+                slacker
+                    .post(create_poll_menu(trigger_id, text))
+                    .map_err(AppError::slack)
+            })
             .map(move |poll| {
                 println!("View response {:?}", poll);
             });
-        actix::spawn(task);
+        self.spawn_for(requester, task);
+    }
+
+    fn read_poll(&self, poll_id: i32) -> impl Future<Item = PollView, Error = AppError> {
+        self.data
+            .send(ReadPoll(poll_id))
+            .map_err(AppError::from)
+            .and_then(|poll| poll)
     }
 
-    pub fn close_poll_and_create_report_request(&self, trigger_id: String) {
+    pub fn close_poll_and_create_report_request(&self, poll_id: i32, requester: Requester) {
         println!("Run report");
-        let app_data = self.clone();
+        let database = self.data.clone();
+        let report_database = self.data.clone();
         let slacker = self.slacker.clone();
+        let message_slacker = self.slacker.clone();
+        let application = self.clone();
+        let user_id = requester.user_id.clone();
         let task = self
-            .data
-            .send(GetPollReport)
-            .map_err(|e| println!("Cannot read report {:?}", e))
-            .map(|e| create_poll_report_view(e.unwrap()))
-            .and_then(move |e| {
-                slacker
-                    .post(e)
-                    .map_err(|e| println!("Cannot post report {}", e))
+            .read_poll(poll_id)
+            .and_then(move |poll| {
+                application
+                    .ensure_admin(user_id, AdminScope::Channel(poll.channel.clone()))
+                    .map(move |_| poll)
             })
-            .map(|result| println!("{:?}", result));
-        actix::spawn(task);
+            .and_then(move |poll| {
+                database
+                    .send(ClosePoll(poll.id.unwrap_or_default()))
+                    .map_err(AppError::from)
+                    .and_then(|poll| poll)
+            })
+            .and_then(move |poll| {
+                let poll_id = poll.id.unwrap_or_default();
+                update_message_response(message_slacker, poll, None, workspace_messages())
+                    .map(move |_| poll_id)
+            })
+            .and_then(move |poll_id| post_report(report_database, slacker, poll_id));
+        self.spawn_for(requester, task);
     }
 
-    pub fn post_last_poll_to_channel(&self, trigger_id: String) {
-        let state = self.state.clone();
+    pub fn post_poll_to_channel(&self, poll_id: i32, requester: Requester) {
         let database = self.data.clone();
         let slacker = self.slacker.clone();
+        let application = self.clone();
+        let user_id = requester.user_id.clone();
         let post_poll = self
-            .data
-            .send(ReadLastPoll)
-            .map_err(|e| println!("Cannot read last poll"))
+            .read_poll(poll_id)
             .and_then(move |poll| {
-                slacker
-                    .post(create_poll_view(poll.unwrap()))
-                    .map_err(|e| println!("Error while request poll {}", e))
+                application
+                    .ensure_admin(user_id, AdminScope::Channel(poll.channel.clone()))
+                    .map(move |_| poll)
             })
-            .and_then(move |resp| {
-                println!("View response {:?}", resp);
-                let mut post = state.lock().unwrap();
+            .and_then(move |poll| {
+                let poll_id = poll.id.unwrap_or_default();
+                // Messages are posted one by one to keep the variants in order
+                futures::stream::iter_ok(create_poll_view(poll, workspace_messages()))
+                    .and_then(move |message| slacker.post(message).map_err(AppError::slack))
+                    .map(|resp| {
+                        println!("View response {:?}", resp);
+                        resp.ts
+                    })
+                    .collect()
+                    .map(move |messages| (poll_id, messages))
+            })
+            .and_then(move |(poll_id, messages)| {
                 database
-                    .send(UpdatePollTime(resp.ts))
-                    .map_err(|e| println!("Cannot write poll {}", e))
+                    .send(SavePollMessages(poll_id, messages))
+                    .map_err(AppError::from)
+                    .and_then(|result| result)
+            });
+        self.spawn_for(requester, post_poll);
+    }
+
+    /// Posts the current results of the poll, voting goes on.
+    /// Polls hiding their voters publish results only once closed.
+    pub fn post_poll_report(&self, poll_id: i32, requester: Requester) {
+        let database = self.data.clone();
+        let slacker = self.slacker.clone();
+        let application = self.clone();
+        let user_id = requester.user_id.clone();
+        let task = self
+            .read_poll(poll_id)
+            .and_then(move |poll| {
+                let poll_id = poll.id.unwrap_or_default();
+                let shows_results = poll.visibility.shows_voters(poll.is_closed);
+                application
+                    .ensure_admin(user_id, AdminScope::Channel(poll.channel))
+                    .and_then(move |_| {
+                        if shows_results {
+                            Ok(poll_id)
+                        } else {
+                            Err(AppError::State(
+                                workspace_messages().report_hidden.to_owned(),
+                            ))
+                        }
+                    })
+            })
+            .and_then(move |poll_id| post_report(database, slacker, poll_id));
+        self.spawn_for(requester, task);
+    }
+
+    /// Shows the state of the poll and of each variant to the requester.
+    pub fn show_poll_status(&self, poll_id: i32, requester: Requester) {
+        let slacker = self.slacker.clone();
+        let application = self.clone();
+        let user_id = requester.user_id.clone();
+        let reply_to = requester.clone();
+        let task = self
+            .read_poll(poll_id)
+            .and_then(move |poll| {
+                application
+                    .ensure_admin(user_id, AdminScope::Channel(poll.channel.clone()))
+                    .map(move |_| poll)
             })
-            .map(|_| ());
-        actix::spawn(post_poll);
+            .and_then(move |poll| {
+                show_ephemeral_message(slacker, reply_to, poll_status_text(poll))
+            });
+        self.spawn_for(requester, task);
     }
 
-    pub fn post_dialog_on_request(&self, block_action: BlockAction) {
+    /// Shows the most recent polls to the requester.
+    pub fn show_poll_list(&self, requester: Requester) {
+        let data = self.data.clone();
+        let slacker = self.slacker.clone();
+        let reply_to = requester.clone();
+        let task = self
+            .ensure_admin(requester.user_id.clone(), AdminScope::AnyChannel)
+            .and_then(move |_| {
+                data.send(ListPolls(RECENT_POLLS))
+                    .map_err(AppError::from)
+                    .and_then(|polls| polls)
+            })
+            .and_then(move |polls| show_ephemeral_message(slacker, reply_to, polls_text(polls)));
+        self.spawn_for(requester, task);
+    }
+
+    /// Publishes the App Home tab of the user. Management buttons are shown for the polls
+    /// in the channels where the user is an admin.
+    pub fn publish_home(&self, user_id: String) {
+        let data = self.data.clone();
+        let owner = self.owner.clone();
+        let api_key = self.api_key.clone();
+        let work_space = self.work_space.clone();
+        let task = self
+            .find_or_create_user(user_id.clone())
+            .join(
+                self.data
+                    .send(ListAdmins)
+                    .map_err(AppError::from)
+                    .and_then(|admins| admins),
+            )
+            .and_then(move |(user, admins)| {
+                data.send(ReadHome(user.id))
+                    .map_err(AppError::from)
+                    .and_then(|home| home)
+                    .map(move |home| (user, admins, home))
+            })
+            .and_then(move |(user, admins, home)| {
+                let is_owner = !owner.is_empty() && owner == user_id;
+                let roles = admins
+                    .into_iter()
+                    .filter(|admin| admin.user_slack_id == user_id)
+                    .map(|admin| admin.channel)
+                    .collect::<Vec<_>>();
+                let blocks = create_home_view(
+                    &home,
+                    |poll| {
+                        is_owner
+                            || roles.iter().any(|channel| match channel {
+                                Some(channel) => *channel == poll.channel,
+                                None => true,
+                            })
+                    },
+                    &work_space,
+                    messages_of(&user),
+                );
+                publish_home_view(&api_key, user_id, blocks)
+            })
+            .map_err(|e| println!("Cannot publish home view {}", e));
+        actix::spawn(task);
+    }
+
+    /// Grants, revokes or lists admin roles. Roles are changed by global admins only.
+    pub fn process_admin_command(&self, command: AdminCommand, requester: Requester) {
+        let data = self.data.clone();
+        let slacker = self.slacker.clone();
+        let owner = self.owner.clone();
+        let reply_to = requester.clone();
+        let scope = match command {
+            AdminCommand::List => AdminScope::AnyChannel,
+            _ => AdminScope::Global,
+        };
+        let task = self
+            .ensure_admin(requester.user_id.clone(), scope)
+            .and_then(move |_| {
+                let text = workspace_messages();
+                let reply: Box<dyn Future<Item = String, Error = AppError>> = match command {
+                    AdminCommand::Grant(user_id, channel) => {
+                        let role = (format!("<@{}>", user_id), admin_scope_text(&channel));
+                        Box::new(
+                            data.send(GrantAdmin(AdminWrite {
+                                user_slack_id: user_id,
+                                channel,
+                            }))
+                            .map_err(AppError::from)
+                            .and_then(|granted| granted)
+                            .map(move |granted| {
+                                let reply = if granted {
+                                    text.admin_granted
+                                } else {
+                                    text.admin_already_granted
+                                };
+                                fill(reply, &[&role.0, &role.1])
+                            }),
+                        )
+                    }
+                    AdminCommand::Revoke(user_id, channel) => {
+                        let role = (format!("<@{}>", user_id), admin_scope_text(&channel));
+                        Box::new(
+                            data.send(RevokeAdmin(user_id, channel))
+                                .map_err(AppError::from)
+                                .and_then(|revoked| revoked)
+                                .map(move |revoked| {
+                                    let reply = if revoked {
+                                        text.admin_revoked
+                                    } else {
+                                        text.admin_not_granted
+                                    };
+                                    fill(reply, &[&role.0, &role.1])
+                                }),
+                        )
+                    }
+                    AdminCommand::List => Box::new(
+                        data.send(ListAdmins)
+                            .map_err(AppError::from)
+                            .and_then(|admins| admins)
+                            .map(move |admins| admins_text(&owner, admins)),
+                    ),
+                };
+                reply
+            })
+            .and_then(move |reply| show_ephemeral_message(slacker, reply_to, reply));
+        self.spawn_for(requester, task);
+    }
+
+    pub fn post_dialog_on_request(&self, block_action: BlockAction) -> Result<(), AppError> {
         let client = self.slacker.clone();
         println!("{:?}", block_action.actions);
-        let action_id = block_action.actions.first().unwrap().action_id.clone();
-        let id = action_id.clone();
-        let dialog_with_poll =
-            self.create_dialog_for_poll(action_id.clone(), block_action.trigger_id.clone());
+        let requester = Requester::from(&block_action);
+        let action_id = block_action
+            .actions
+            .first()
+            .map(|action| action.action_id.clone())
+            .ok_or_else(|| AppError::Parse("Block action without actions".to_owned()))?;
+        let key = VariantKey::from_str(&action_id)
+            .map_err(|_| AppError::Parse(format!("Unknown vote action {}", action_id)))?;
+        let application = self.clone();
 
         let answer = self
             .data
-            .send(ReadVotesForCurrentUser(block_action.user.id.clone()))
-            .map_err(|e| println!("Cannot read votes for current user {}", e))
+            .send(ReadVotesForCurrentUser(
+                key.poll_id,
+                block_action.user.id.clone(),
+            ))
+            .map_err(AppError::from)
+            .and_then(|votes| votes)
             .map(move |votes| {
                 votes
-                    .expect("Cannot find votes in database")
-                    .iter()
-                    .find(|e| e.poll_variant_id == i32::from_str(id.as_str()).unwrap())
-                    .is_none()
+                    .into_iter()
+                    .filter(|e| e.poll_variant_id == key.variant_id)
+                    .collect::<Vec<_>>()
             })
-            .join(
+            .join4(
                 self.data
-                    .send(ReadPollVariant(
-                        i32::from_str(action_id.clone().as_str()).unwrap_or(1),
-                    ))
-                    .map_err(|e| println!("Cannot find this variant {}", e)),
+                    .send(ReadPollVariant(key.poll_id, key.variant_id))
+                    .map_err(AppError::from)
+                    .and_then(|variant| variant),
+                self.read_poll(key.poll_id),
+                self.find_or_create_user(block_action.user.id.clone()),
             )
-            .and_then(move |is_available| {
-                println!("Start choose {:?}", is_available);
-                let start_time = is_available.1.unwrap();
+            .and_then(move |(previous_votes, start_time, poll, user)| {
                 println!("Start choose {:?}", start_time);
-                let now = Local::now().naive_local();
+                let now = now_utc();
                 println!("Start choose {:?}", now);
-                if is_available.0 && start_time.start_date < now {
-                    println!("Ok");
-                    dialog_with_poll
-                } else if !is_available.0 {
-                    println!("Voted");
-                    show_answered_request_view(client, block_action)
+                let text = messages_of(&user);
+                let time_zone = UserTimeZone::from(&user);
+                if poll.is_closed {
+                    println!("Closed");
+                    show_closed_request_view(client, block_action, text)
+                } else if start_time.is_voting_ended(now) {
+                    println!("Ended");
+                    show_finished_request_view(
+                        client,
+                        block_action,
+                        time_zone.format(start_time.end_date.unwrap_or(now)),
+                        text,
+                    )
+                } else if start_time.start_date < now {
+                    println!("Ok, previous votes {:?}", previous_votes);
+                    application.open_ballot(
+                        key,
+                        poll.is_anonymous,
+                        block_action,
+                        start_time,
+                        previous_votes,
+                        text,
+                    )
                 } else {
                     println!("Time");
-                    show_not_ready_request_view(client, block_action, start_time.start_date)
+                    show_not_ready_request_view(
+                        client,
+                        block_action,
+                        time_zone.format(start_time.start_date),
+                        text,
+                    )
                 }
             });
-        actix::spawn(answer);
+        self.spawn_for(requester, answer);
+        Ok(())
     }
 
-    fn create_dialog_for_poll(
+    /// Opens the modal with every voter of the variant whose "who voted" button was pressed.
+    pub fn show_voters(&self, block_action: BlockAction) -> Result<(), AppError> {
+        let action_id = block_action
+            .actions
+            .first()
+            .map(|action| action.action_id.clone())
+            .unwrap_or_default();
+        let key = VariantKey::from_id_with_prefix(WHO_VOTED_ACTION_PREFIX, &action_id)
+            .map_err(|_| AppError::Parse(format!("Unknown who voted action {}", action_id)))?;
+        let requester = Requester::from(&block_action);
+        let slacker = self.slacker.clone();
+        let trigger_id = block_action.trigger_id;
+        let task = self
+            .read_poll(key.poll_id)
+            .join(self.user_messages(block_action.user.id))
+            .and_then(move |(poll, text)| {
+                if !poll.visibility.shows_voters(poll.is_closed) {
+                    return Err(AppError::State(text.voters_hidden.to_owned()));
+                }
+                let variant = poll
+                    .variants
+                    .into_iter()
+                    .find(|variant| variant.id == Some(key.variant_id))
+                    .ok_or_else(|| AppError::State(text.variant_not_found.to_owned()))?;
+                Ok(create_voters_view(trigger_id, &variant, text))
+            })
+            .and_then(move |view| slacker.post(view).map_err(AppError::slack))
+            .map(|result| println!("{:?}", result));
+        self.spawn_for(requester, task);
+        Ok(())
+    }
+
+    /// Opens the scoring modal of the variant. Anonymous ballots are not linked to the user,
+    /// so they cannot be edited: a voter who already scored the variant is told so instead.
+    fn open_ballot(
         &self,
-        action_id: String,
-        trigger_id: String,
-    ) -> Box<dyn Future<Item = (), Error = ()>> {
+        key: VariantKey,
+        is_anonymous: bool,
+        block_action: BlockAction,
+        variant: SingleVariantSource,
+        previous_votes: Vec<VotesResult>,
+        text: &'static Messages,
+    ) -> Box<dyn Future<Item = (), Error = AppError>> {
+        if !is_anonymous {
+            return self.create_dialog_for_poll(
+                key,
+                block_action.user.id,
+                block_action.trigger_id,
+                variant,
+                previous_votes,
+                text,
+            );
+        }
+        let hash = match self.anonymous_key {
+            Some(ref anonymous_key) => {
+                voter_hash(anonymous_key, key.poll_id, &block_action.user.id)
+            }
+            None => {
+                return Box::new(futures::future::err(AppError::State(
+                    text.anonymous_key_missing.to_owned(),
+                )))
+            }
+        };
         let client = self.slacker.clone();
+        let application = self.clone();
         Box::new(
             self.data
-                .send(ReadDialogVariantsForLastDay)
-                .map(|variants| Into::<DialogView>::into(variants.unwrap()))
-                .map_err(|e| println!("Cannot read from database dialog variants {}", e))
-                .join(
-                    self.data
-                        .send(ReadPollVariant(i32::from_str(action_id.as_str()).unwrap()))
-                        .map_err(|e| println!("Cannot read from database day variants {}", e)),
-                )
-                .and_then(move |result| {
-                    let view = result.0;
-                    let variant = result.1.unwrap();
-                    let mut dialog;
-                    if variant.title.as_ref().unwrap_or(&Default::default()).len() >= 24 {
-                        dialog = Dialog::new_dialog_with_callback(
-                            format!(
-                                "{}...",
-                                variant
-                                    .title
-                                    .clone()
-                                    .unwrap_or(Default::default())
-                                    .drain(0..=20)
-                                    .collect::<String>()
-                            )
-                            .as_str(),
-                            &action_id,
-                            "Подтвердить",
-                        );
+                .send(HasAnonymousBallot(key.variant_id, hash))
+                .map_err(AppError::from)
+                .and_then(|voted| voted)
+                .and_then(move |voted| {
+                    if voted {
+                        show_anonymous_voted_request_view(client, block_action, text)
                     } else {
-                        dialog = Dialog::new_dialog_with_callback(
-                            format!("{}", variant.title.clone().unwrap_or(Default::default()))
-                                .as_str(),
-                            &action_id,
-                            "Подтвердить",
-                        );
+                        application.create_dialog_for_poll(
+                            key,
+                            block_action.user.id,
+                            block_action.trigger_id,
+                            variant,
+                            previous_votes,
+                            text,
+                        )
                     }
-                    for variant in view.variants {
-                        let mut options = vec![];
-                        for i in variant.max_score {
-                            options.push(i.to_string())
-                        }
-                        dialog =
-                            dialog.add_element(DialogElement::new_select_element_with_options(
-                                &variant.variant_text,
-                                &variant.variant_text,
-                                options,
-                            ));
+                }),
+        )
+    }
+
+    /// Opens the scoring modal with the criteria of the track of the user, prefilled with
+    /// `previous_votes` when the user edits a ballot.
+    fn create_dialog_for_poll(
+        &self,
+        key: VariantKey,
+        user_slack_id: String,
+        trigger_id: String,
+        variant: SingleVariantSource,
+        previous_votes: Vec<VotesResult>,
+        text: &'static Messages,
+    ) -> Box<dyn Future<Item = (), Error = AppError>> {
+        let client = self.slacker.clone();
+        let callback_id = if previous_votes.is_empty() {
+            key.to_action_id()
+        } else {
+            key.to_id_with_prefix(EDIT_VOTE_ACTION_PREFIX)
+        };
+        let previous_scores = previous_votes
+            .iter()
+            .map(|vote| (vote.dialog_variant_id, vote.score))
+            .collect::<HashMap<_, _>>();
+        Box::new(
+            self.data
+                .send(ReadVoterCriteria(key.poll_id, user_slack_id))
+                .map_err(AppError::from)
+                .and_then(|criteria| criteria)
+                .and_then(move |criteria| {
+                    if criteria.is_empty() {
+                        return Either::A(futures::future::err(AppError::State(
+                            text.no_criteria_for_track.to_owned(),
+                        )));
                     }
-                    client
-                        .post(DialogOpen::new(&trigger_id, dialog))
-                        .map_err(|e| println!("Cannot post request to dialog {}", e))
+                    Either::B(
+                        client
+                            .post(create_vote_view(
+                                trigger_id,
+                                &callback_id,
+                                &variant,
+                                &criteria,
+                                &previous_scores,
+                                text,
+                            ))
+                            .map_err(AppError::slack),
+                    )
                 })
                 .map(|e| println!("{:?}", e)),
         )
     }
 
-    pub fn add_variant_to_poll(&self, mut old_view: View) {
+    pub fn add_variant_to_poll(&self, mut old_view: View, requester: Requester) {
         let count = old_view
             .blocks
             .iter()
             .filter(|e| {
-                if let LayoutBlock::Input { .. } = e {
-                    true
+                if let LayoutBlock::Input { block_id, .. } = e {
+                    is_variant_input(block_id)
                 } else {
                     false
                 }
             })
             .count();
-        let next_id = count / 3 + 1;
-        old_view.blocks.insert(
-            old_view.blocks.len() - 2,
-            LayoutBlock::new_plain_single_line_text_input(
-                format!("Заголовок #{}", next_id).as_str(),
-                format!("title_text_{}", next_id),
-                "Можно в markdown",
-            ),
-        );
-        old_view.blocks.insert(
-            old_view.blocks.len() - 2,
-            LayoutBlock::new_plain_text_input(
-                format!("Вариант #{}", next_id).as_str(),
-                format!("variant_text_{}", next_id),
-            ),
-        );
-        old_view.blocks.insert(
-            old_view.blocks.len() - 2,
-            LayoutBlock::new_plain_single_line_text_input(
-                format!("Дата начала голосования #{}", next_id).as_str(),
-                format!("start_variant_poll_date_{}", next_id),
-                "2015-09-18T23:56:04",
-            ),
-        );
-        let mut id = old_view.id.clone();
-        let mut submit = old_view.submit.clone();
-        let mut update_view = ViewUpdate::new(old_view);
-        update_view.view_id = id;
-        update_view = update_view.add_submit(submit.unwrap());
+        let next_id = count / VARIANT_INPUTS + 1;
+        let slacker = self.slacker.clone();
         let update = self
-            .slacker
-            .post(update_view)
-            .map(|result| println!("Post update view result {:?}", result))
-            .map_err(|e| println!("Cannot update poll creator {}", e));
-        actix::spawn(update);
-    }
-
-    pub fn process_channel_change(&self, channel_id: String) {
-        use std::sync::LockResult;
-        match self.state.lock() {
-            Result::Ok(mut guard) => guard.as_mut().unwrap().poll_channel = channel_id,
-            Result::Err(err) => err.into_inner().as_mut().unwrap().poll_channel = channel_id,
-        }
+            .ensure_admin(requester.user_id.clone(), AdminScope::AnyChannel)
+            .join(self.user_messages(requester.user_id.clone()))
+            .and_then(move |(_, text)| {
+                for block in create_variant_inputs(next_id, text) {
+                    old_view.blocks.insert(old_view.blocks.len() - 2, block);
+                }
+                let mut id = old_view.id.clone();
+                let mut submit = old_view.submit.clone();
+                let mut update_view = ViewUpdate::new(old_view);
+                update_view.view_id = id;
+                if let Some(submit) = submit {
+                    update_view = update_view.add_submit(submit);
+                }
+                slacker.post(update_view).map_err(AppError::slack)
+            })
+            .map(|result| println!("Post update view result {:?}", result));
+        self.spawn_for(requester, update);
     }
 
-    pub fn show_dialog_create(&self, trigger_id: String) {
-        let blocks = vec![
-            LayoutBlock::new_plain_single_line_text_input(
-                "Критерий #1",
-                "dialog_variant_text_1".to_owned(),
-                "Критерий оценки голоса",
-            ),
-            LayoutBlock::new_plain_single_line_text_input(
-                "Максимальная оценка #1",
-                "dialog_variant_max_score_1".to_owned(),
-                "1-100",
-            ),
-            LayoutBlock::new_action(vec![BlockElement::new_button(
-                "Добавить критерий",
-                "dialog_variant_add".to_owned(),
-            )])
-            .build(),
-        ];
-        let view_push = ViewPush::new_with_id(
-            trigger_id,
-            DIALOG_VARIANT_CREATE_ID,
-            "Варианты оценок",
-            blocks,
-        )
-        .add_submit("Accept");
+    pub fn process_channel_change(
+        &self,
+        requester: Requester,
+        view_id: String,
+        channel_id: String,
+    ) {
+        let data = self.data.clone();
+        let user_id = requester.user_id.clone();
+        let save_channel = self
+            .ensure_admin(user_id.clone(), AdminScope::AnyChannel)
+            .and_then(move |_| {
+                data.send(SaveDraftChannel(user_id, view_id, channel_id))
+                    .map_err(AppError::from)
+                    .and_then(|result| result)
+            });
+        self.spawn_for(requester, save_channel);
+    }
+
+    /// Pushes the criteria view. Its callback id carries the root view id,
+    /// so the criteria end up in the draft of the poll being created.
+    pub fn show_dialog_create(
+        &self,
+        trigger_id: String,
+        root_view_id: String,
+        requester: Requester,
+    ) {
+        let slacker = self.slacker.clone();
         let push_view = self
-            .slacker
-            .post(view_push)
-            .map_err(|e| println!("Error while push view {}", e))
+            .ensure_admin(requester.user_id.clone(), AdminScope::AnyChannel)
+            .join(self.user_messages(requester.user_id.clone()))
+            .and_then(move |(_, text)| {
+                let mut blocks = create_criterion_inputs(1, text);
+                blocks.push(
+                    LayoutBlock::new_action(vec![BlockElement::new_button(
+                        text.add_criterion,
+                        "dialog_variant_add".to_owned(),
+                    )])
+                    .build(),
+                );
+                let view_push = ViewPush::new_with_id(
+                    trigger_id,
+                    format!("{}:{}", DIALOG_VARIANT_CREATE_ID, root_view_id).as_str(),
+                    text.criteria,
+                    blocks,
+                )
+                .add_submit(text.accept);
+                slacker.post(view_push).map_err(AppError::slack)
+            })
             .map(|resp| println!("Response {:?}", resp));
-        actix::spawn(push_view);
+        self.spawn_for(requester, push_view);
     }
 
-    pub fn add_variant_to_dialog(&self, mut old_view: View) {
+    pub fn add_variant_to_dialog(&self, mut old_view: View, requester: Requester) {
         println!("Update dialog view");
         let count = old_view
             .blocks
@@ -387,160 +858,592 @@ impl SlackApplication {
                 }
             })
             .count();
-        let next_id = count / 2 + 1;
-        old_view.blocks.insert(
-            old_view.blocks.len() - 1,
-            LayoutBlock::new_plain_single_line_text_input(
-                format!("Критерий #{}", next_id).as_str(),
-                format!("dialog_variant_text_{}", next_id),
-                "Критерий оценки голоса",
-            ),
-        );
-        old_view.blocks.insert(
-            old_view.blocks.len() - 1,
-            LayoutBlock::new_plain_single_line_text_input(
-                format!("Максимальная оценка #{}", next_id).as_str(),
-                format!("dialog_variant_max_score_{}", next_id),
-                "1-100",
-            ),
-        );
-        let mut id = old_view.id.clone();
-        let mut submit = old_view.submit.clone();
-        let mut update_view = ViewUpdate::new(old_view);
-        update_view.view_id = id;
-        update_view = update_view.add_submit(submit.unwrap());
+        let next_id = count / CRITERION_INPUTS + 1;
+        let slacker = self.slacker.clone();
         let update = self
-            .slacker
-            .post(update_view)
-            .map(|result| println!("Post update view result {:?}", result))
-            .map_err(|e| println!("Cannot update poll creator {}", e));
-        actix::spawn(update);
+            .ensure_admin(requester.user_id.clone(), AdminScope::AnyChannel)
+            .join(self.user_messages(requester.user_id.clone()))
+            .and_then(move |(_, text)| {
+                for block in create_criterion_inputs(next_id, text) {
+                    old_view.blocks.insert(old_view.blocks.len() - 1, block);
+                }
+                let mut id = old_view.id.clone();
+                let mut submit = old_view.submit.clone();
+                let mut update_view = ViewUpdate::new(old_view);
+                update_view.view_id = id;
+                if let Some(submit) = submit {
+                    update_view = update_view.add_submit(submit);
+                }
+                slacker.post(update_view).map_err(AppError::slack)
+            })
+            .map(|result| println!("Post update view result {:?}", result));
+        self.spawn_for(requester, update);
     }
 
     //todo убрать дубли если все работает
-    pub fn save_dialog_info(&self, block_action: BlockAction) {
-        let mut view = block_action.view.unwrap();
-        let mut values = view.state.unwrap().values;
-        let mut state = self.state.lock().unwrap();
-        let variants = &mut state.as_mut().unwrap().dialog_variants;
-        let mut peekable = view
+    pub fn save_dialog_info(&self, block_action: BlockAction) -> Result<(), AppError> {
+        let requester = Requester::from(&block_action);
+        let view = block_action
+            .view
+            .ok_or_else(|| AppError::Parse("Criteria submission without view".to_owned()))?;
+        let root_view_id = view
+            .callback_id
+            .clone()
+            .unwrap_or_default()
+            .trim_start_matches(DIALOG_VARIANT_CREATE_ID)
+            .trim_start_matches(':')
+            .to_owned();
+        let mut values = view
+            .state
+            .ok_or_else(|| AppError::Parse("Criteria submission without state".to_owned()))?
+            .values;
+        let mut variants = vec![];
+        let mut errors = ValidationErrors::new();
+        let inputs = view
             .blocks
             .iter()
-            .filter(|e| {
-                if let LayoutBlock::Input { .. } = e {
-                    true
+            .filter_map(|e| {
+                if let LayoutBlock::Input { block_id, .. } = e {
+                    Some(block_id.clone())
                 } else {
-                    false
+                    None
                 }
             })
-            .peekable();
-        while let (Option::Some(variant), Option::Some(date)) = (peekable.next(), peekable.peek()) {
-            if let (
-                LayoutBlock::Input { block_id, .. },
-                LayoutBlock::Input {
-                    block_id: date_id, ..
-                },
-            ) = (variant, date)
-            {
-                let variant = values[block_id.as_ref().unwrap()][block_id.as_ref().unwrap()]
-                    .as_object_mut()
-                    .unwrap_or(&mut Map::new())
-                    .remove("value");
-                let start_date = values[date_id.as_ref().unwrap()][date_id.as_ref().unwrap()]
-                    .as_object_mut()
-                    .unwrap_or(&mut Map::new())
-                    .remove("value");
-                if let (Option::Some(data), Option::Some(score)) =
-                    (variant.to_owned(), start_date.to_owned())
-                {
+            .collect::<Vec<_>>();
+        for criterion_inputs in inputs.chunks(CRITERION_INPUTS) {
+            if let [text_id, score_id, weight_id, track_id] = criterion_inputs {
+                let variant = take_input_value(&mut values, text_id);
+                let score = take_input_value(&mut values, score_id);
+                let weight = take_input_value(&mut values, weight_id);
+                let variant = check(
+                    &mut errors,
+                    text_id,
+                    parse_text(variant.as_ref().and_then(Value::as_str)),
+                );
+                let score = check(
+                    &mut errors,
+                    score_id,
+                    parse_max_score(score.as_ref().and_then(Value::as_str)),
+                );
+                let weight = check(
+                    &mut errors,
+                    weight_id,
+                    parse_weight(weight.as_ref().and_then(Value::as_str)),
+                );
+                let track = track_id
+                    .as_ref()
+                    .and_then(|track_id| selected_value(&mut values, track_id))
+                    .and_then(|track| Track::from_str(&track).ok())
+                    .unwrap_or_default();
+                if let (Some(data), Some(score), Some(weight)) = (variant, score, weight) {
                     variants.push(DialogViewVariant {
-                        variant_text: data.as_str().unwrap().to_owned(),
-                        max_score: 1..=i32::from_str(score.as_str().unwrap()).unwrap_or(1),
+                        variant_text: data,
+                        max_score: MIN_SCORE..=score,
+                        weight,
+                        track,
                     })
                 }
             }
-            peekable.next();
         }
+        if !errors.is_empty() {
+            return Err(AppError::Validation(errors));
+        }
+        let data = self.data.clone();
+        let author = block_action.user.id;
+        let save_variants = self
+            .ensure_admin(author.clone(), AdminScope::AnyChannel)
+            .and_then(move |_| {
+                data.send(SaveDraftDialogVariants(author, root_view_id, variants))
+                    .map_err(AppError::from)
+                    .and_then(|result| result)
+            });
+        self.spawn_for(requester, save_variants);
+        Ok(())
     }
 
-    pub fn save_poll_info(&self, block_action: BlockAction) {
-        let mut view = block_action.view.unwrap();
+    /// Validates the poll creation view, the jury is checked against the criteria of the
+    /// draft before the view is closed. The poll itself is written in background.
+    pub fn save_poll_info(
+        &self,
+        block_action: BlockAction,
+    ) -> Result<Box<dyn Future<Item = HttpResponse, Error = AppError>>, AppError> {
+        let requester = Requester::from(&block_action);
+        let view = block_action
+            .view
+            .ok_or_else(|| AppError::Parse("Poll submission without view".to_owned()))?;
         println!("{:?}", view);
-        let mut values = view.state.unwrap().values;
-        let mut lock = self.state.lock().unwrap();
-        let mut state = lock.as_mut().unwrap();
-        let poll_variants = &mut state.poll_variants;
-        let mut peekable = view
+        let view_id = view.id.clone().unwrap_or_default();
+        let mut values = view
+            .state
+            .ok_or_else(|| AppError::Parse("Poll submission without state".to_owned()))?
+            .values;
+        let mut poll_variants = vec![];
+        let mut errors = ValidationErrors::new();
+        let inputs = view
             .blocks
             .iter()
-            .filter(|e| {
-                if let LayoutBlock::Input { .. } = e {
-                    true
-                } else {
-                    false
+            .filter_map(|e| match e {
+                LayoutBlock::Input { block_id, .. } if is_variant_input(block_id) => {
+                    Some(block_id.clone())
                 }
+                _ => None,
             })
-            .peekable();
-        while let (Option::Some(title), Option::Some(variant), Option::Some(date)) =
-            (peekable.next(), peekable.next(), peekable.peek())
-        {
-            println!("{:?} {:?} {:?}", title, variant, date);
-            if let (
-                LayoutBlock::Input {
-                    block_id: title_id, ..
-                },
-                LayoutBlock::Input { block_id, .. },
-                LayoutBlock::Input {
-                    block_id: date_id, ..
-                },
-            ) = (title, variant, date)
+            .collect::<Vec<_>>();
+        let channel = selected_channel(&values);
+        let visibility = selected_value(&mut values, POLL_VISIBILITY_INPUT)
+            .and_then(|value| PollVisibility::from_str(&value).ok())
+            .unwrap_or_default();
+        let is_anonymous = selected_value(&mut values, POLL_BALLOT_INPUT)
+            .as_ref()
+            .map(String::as_str)
+            == Some(BALLOT_ANONYMOUS);
+        let jury = take_input_field(
+            &mut values,
+            &Some(POLL_JURY_INPUT.to_owned()),
+            "selected_users",
+        )
+        .and_then(|users| serde_json::from_value::<Vec<String>>(users).ok())
+        .unwrap_or_default();
+        if is_anonymous && self.anonymous_key.is_none() {
+            errors.insert(
+                POLL_BALLOT_INPUT.to_owned(),
+                workspace_messages().anonymous_key_missing.to_owned(),
+            );
+        }
+        if channel.is_none() {
+            // The channel select lives in an actions block, Slack shows errors only under inputs
+            if let Some(block_id) = inputs.first() {
+                errors.insert(
+                    block_id.clone().unwrap_or_default(),
+                    workspace_messages().select_channel_first.to_owned(),
+                );
+            }
+        }
+        for variant_inputs in inputs.chunks(VARIANT_INPUTS) {
+            if let [title_id, variant_id, start_date_id, start_time_id, end_date_id, end_time_id] =
+                variant_inputs
             {
-                println!("{:?} {:?} {:?}", title_id, block_id, date_id);
-                let title = values[title_id.as_ref().unwrap()][title_id.as_ref().unwrap()]
-                    .as_object_mut()
-                    .unwrap_or(&mut Map::new())
-                    .remove("value");
-                let variant = values[block_id.as_ref().unwrap()][block_id.as_ref().unwrap()]
-                    .as_object_mut()
-                    .unwrap_or(&mut Map::new())
-                    .remove("value");
-                let start_date = values[date_id.as_ref().unwrap()][date_id.as_ref().unwrap()]
-                    .as_object_mut()
-                    .unwrap_or(&mut Map::new())
-                    .remove("value");
-                if let (Option::Some(title), Option::Some(variant), Option::Some(date)) =
-                    (title.to_owned(), variant.to_owned(), start_date.to_owned())
+                let title = take_input_value(&mut values, title_id);
+                let variant = take_input_value(&mut values, variant_id);
+                let start_date = take_input_field(&mut values, start_date_id, "selected_date");
+                let start_time = take_input_field(&mut values, start_time_id, "selected_time");
+                let end_date = take_input_field(&mut values, end_date_id, "selected_date");
+                let end_time = take_input_field(&mut values, end_time_id, "selected_time");
+                let title = check(
+                    &mut errors,
+                    title_id,
+                    parse_text(title.as_ref().and_then(Value::as_str)),
+                );
+                let start_date = check(
+                    &mut errors,
+                    start_date_id,
+                    parse_picked_date(start_date.as_ref().and_then(Value::as_str)),
+                );
+                let start_time = check(
+                    &mut errors,
+                    start_time_id,
+                    parse_picked_time(start_time.as_ref().and_then(Value::as_str)),
+                );
+                let end_date = check(
+                    &mut errors,
+                    end_date_id,
+                    parse_window_end(
+                        end_date.as_ref().and_then(Value::as_str),
+                        end_time.as_ref().and_then(Value::as_str),
+                    ),
+                );
+                if let (Some(title), Some(start_date), Some(start_time), Some(end_date)) =
+                    (title, start_date, start_time, end_date)
                 {
-                    poll_variants.push(SingleVariant {
-                        id: None,
-                        title: title.as_str().unwrap_or(Default::default()).to_owned(),
-                        variant: variant.as_str().unwrap_or(Default::default()).to_owned(),
-                        images: vec![],
-                        votes: None,
-                        start_date: NaiveDateTime::from_str(date.as_str().unwrap())
-                            .unwrap_or(NaiveDateTime::from_timestamp(0, 0)),
-                    })
+                    let start_date = start_date.and_time(start_time);
+                    if end_date.map(|end| end <= start_date).unwrap_or(false) {
+                        errors.insert(
+                            end_date_id.clone().unwrap_or_default(),
+                            workspace_messages().end_before_start.to_owned(),
+                        );
+                    } else {
+                        poll_variants.push(PickedVariant {
+                            title,
+                            variant: variant
+                                .as_ref()
+                                .and_then(Value::as_str)
+                                .unwrap_or_default()
+                                .to_owned(),
+                            start_date,
+                            end_date,
+                        })
+                    }
                 }
             }
-            peekable.next();
         }
-        drop(lock);
-        let state = self
-            .state
-            .lock()
-            .unwrap()
-            .replace(PollData::default())
-            .unwrap();
-        println!("{:?}", state);
-        let self_state = self.state.clone();
+        if !errors.is_empty() {
+            return Err(AppError::Validation(errors));
+        }
         let database = self.data.clone();
-        let slacker = self.slacker.clone();
-        let write_time_access = self.data.clone();
-        let write_poll = self
+        let draft_database = self.data.clone();
+        let author = block_action.user.id;
+        let has_jury = !jury.is_empty();
+        let check_tracks = self
             .data
-            .send(WriteNewPoll(state))
-            .map_err(|e| println!("Cannot write poll {}", e))
-            .map(|e| ());
-        actix::spawn(write_poll);
+            .send(ReadPollDraft(author.clone(), view_id.clone()))
+            .map_err(AppError::from)
+            .and_then(|draft| draft)
+            .and_then(move |draft| {
+                check_jury_tracks(has_jury, &draft.dialog_variants).map_err(AppError::State)
+            })
+            .map_err(|error| match error {
+                // Neither the draft nor its criteria have an input in the poll creation view,
+                // their errors are shown under the jury input
+                AppError::State(message) => {
+                    let mut errors = ValidationErrors::new();
+                    errors.insert(POLL_JURY_INPUT.to_owned(), message);
+                    AppError::Validation(errors)
+                }
+                error => error,
+            });
+        let write_poll = self
+            .ensure_admin(
+                author.clone(),
+                AdminScope::Channel(channel.clone().unwrap_or_default()),
+            )
+            .join(self.user_time_zone(author.clone()))
+            .and_then(move |(_, time_zone)| {
+                // Picked dates are wall clock times of the admin, the database keeps UTC
+                let to_utc = |local: NaiveDateTime| {
+                    time_zone.to_utc(local).ok_or_else(|| {
+                        AppError::State(fill(
+                            workspace_messages().skipped_time,
+                            &[&local.format("%Y-%m-%d %H:%M")],
+                        ))
+                    })
+                };
+                poll_variants
+                    .into_iter()
+                    .map(|variant: PickedVariant| {
+                        Ok(SingleVariant {
+                            id: None,
+                            title: variant.title,
+                            variant: variant.variant,
+                            voters: vec![],
+                            votes: None,
+                            start_date: to_utc(variant.start_date)?,
+                            end_date: match variant.end_date {
+                                Some(end_date) => Some(to_utc(end_date)?),
+                                None => None,
+                            },
+                        })
+                    })
+                    .collect::<Result<Vec<_>, AppError>>()
+            })
+            .and_then(move |poll_variants| {
+                draft_database
+                    .send(TakePollDraft(author, view_id))
+                    .map_err(AppError::from)
+                    .and_then(|draft| draft)
+                    .map(move |draft| (draft, poll_variants))
+            })
+            .and_then(move |(mut state, poll_variants)| {
+                state.poll_channel = channel.unwrap_or_default();
+                state.visibility = visibility;
+                state.is_anonymous = is_anonymous;
+                state.jury = jury;
+                state.poll_variants = poll_variants;
+                println!("{:?}", state);
+                database
+                    .send(WriteNewPoll(state))
+                    .map_err(AppError::from)
+                    .and_then(|poll_id| poll_id)
+            })
+            .map(|poll_id| println!("Poll {} created", poll_id));
+        let application = self.clone();
+        Ok(Box::new(check_tracks.map(move |_| {
+            application.spawn_for(requester, write_poll);
+            HttpResponse::Ok().finish()
+        })))
+    }
+}
+
+/// What a submitted scoring modal asks for.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum BallotSubmission {
+    New,
+    /// Replaces the scores of the ballot
+    Edit,
+    Withdraw,
+}
+
+/// Variant and kind of a scoring modal submission. Only the modal of an existing ballot,
+/// opened with `EDIT_VOTE_ACTION_PREFIX`, can withdraw it.
+fn ballot_submission(
+    callback_id: &str,
+    values: &mut Value,
+) -> Result<(VariantKey, BallotSubmission), AppError> {
+    if let Ok(key) = VariantKey::from_id_with_prefix(EDIT_VOTE_ACTION_PREFIX, callback_id) {
+        let withdraw = selected_value(values, WITHDRAW_VOTE_INPUT)
+            .map(|answer| answer == WITHDRAW_VOTE_YES)
+            .unwrap_or(false);
+        let submission = if withdraw {
+            BallotSubmission::Withdraw
+        } else {
+            BallotSubmission::Edit
+        };
+        return Ok((key, submission));
+    }
+    VariantKey::from_str(callback_id)
+        .map(|key| (key, BallotSubmission::New))
+        .map_err(|_| AppError::Parse(format!("Unknown vote callback {}", callback_id)))
+}
+
+/// Reads the report of the poll and posts it to the poll channel.
+fn post_report(
+    data: Addr<Database>,
+    slacker: Slacker,
+    poll_id: i32,
+) -> impl Future<Item = (), Error = AppError> {
+    data.send(GetPollReport(poll_id))
+        .map_err(AppError::from)
+        .and_then(|report| report)
+        .and_then(|report| {
+            if report.is_empty() {
+                Err(AppError::State(
+                    workspace_messages().empty_report.to_owned(),
+                ))
+            } else {
+                Ok(create_poll_report_view(report, workspace_messages()))
+            }
+        })
+        .and_then(move |messages| {
+            // Messages are posted one by one to keep the places in order
+            futures::stream::iter_ok(messages)
+                .and_then(move |message| slacker.post(message).map_err(AppError::slack))
+                .for_each(|result| {
+                    println!("{:?}", result);
+                    Ok(())
+                })
+        })
+}
+
+fn poll_state_text(is_closed: bool, is_posted: bool) -> &'static str {
+    let text = workspace_messages();
+    if is_closed {
+        text.poll_state_closed
+    } else if is_posted {
+        text.poll_state_open
+    } else {
+        text.poll_state_draft
+    }
+}
+
+/// Header line of the poll and one line per variant, dates are rendered by Slack
+/// in the timezone of the reader.
+fn poll_status_text(poll: PollView) -> String {
+    let text = workspace_messages();
+    let mut lines = vec![fill(
+        text.poll_status,
+        &[
+            &poll.id.unwrap_or_default(),
+            &format!("<#{}>", poll.channel),
+            &poll_state_text(poll.is_closed, !poll.messages.is_empty()),
+        ],
+    )];
+    // Admins see the counts only when the poll messages show them
+    let shows_counts = poll.visibility.shows_counts(poll.is_closed);
+    lines.extend(poll.variants.into_iter().map(|variant| {
+        let votes = match variant.votes {
+            _ if !shows_counts => text.votes_hidden.to_owned(),
+            Some(count) => fill(text.votes_count, &[&count]),
+            None => text.no_votes.to_owned(),
+        };
+        let window = match variant.end_date {
+            Some(end_date) => fill(
+                text.variant_window,
+                &[&slack_date(variant.start_date), &slack_date(end_date)],
+            ),
+            None => fill(text.variant_window_from, &[&slack_date(variant.start_date)]),
+        };
+        format!(
+            "• {}",
+            fill(text.variant_status, &[&variant.title, &votes, &window])
+        )
+    }));
+    lines.join("\n")
+}
+
+fn polls_text(polls: Vec<PollSummary>) -> String {
+    let text = workspace_messages();
+    if polls.is_empty() {
+        return text.no_polls.to_owned();
+    }
+    let lines = polls
+        .into_iter()
+        .map(|summary| {
+            format!(
+                "• {}",
+                fill(
+                    text.poll_summary,
+                    &[
+                        &summary.poll.id,
+                        &format!("<#{}>", summary.poll.channel),
+                        &poll_state_text(summary.poll.is_closed, summary.is_posted),
+                        &summary.variants,
+                        &summary.voters,
+                    ],
+                )
+            )
+        })
+        .collect::<Vec<_>>();
+    format!("{}\n{}", text.polls_title, lines.join("\n"))
+}
+
+fn admin_scope_text(channel: &Option<String>) -> String {
+    let text = workspace_messages();
+    match channel {
+        Some(channel) => fill(text.admin_in_channel, &[&format!("<#{}>", channel)]),
+        None => text.admin_everywhere.to_owned(),
+    }
+}
+
+/// One line per role, the owner from the configuration goes first.
+fn admins_text(owner: &str, admins: Vec<Admin>) -> String {
+    let text = workspace_messages();
+    let mut lines = vec![];
+    if !owner.is_empty() {
+        lines.push(fill(text.admin_owner, &[&format!("<@{}>", owner)]));
+    }
+    lines.extend(admins.into_iter().map(|admin| {
+        format!(
+            "<@{}> {}",
+            admin.user_slack_id,
+            admin_scope_text(&admin.channel)
+        )
+    }));
+    if lines.is_empty() {
+        return text.admins_empty.to_owned();
+    }
+    let lines = lines
+        .iter()
+        .map(|line| format!("• {}", line))
+        .collect::<Vec<_>>();
+    format!("{}\n{}", text.admins_title, lines.join("\n"))
+}
+
+/// Texts in the Slack locale of the user, the workspace locale is used when it is unknown
+/// or not supported.
+fn messages_of(user: &ChannelUser) -> &'static Messages {
+    user.locale
+        .as_ref()
+        .and_then(|locale| Locale::from_slack(locale))
+        .unwrap_or_else(Locale::workspace)
+        .messages()
+}
+
+/// Tells the variant inputs of the poll creation view from the poll settings.
+fn is_variant_input(block_id: &Option<String>) -> bool {
+    block_id
+        .as_ref()
+        .map(|id| !POLL_SETTING_INPUTS.contains(&id.as_str()))
+        .unwrap_or(true)
+}
+
+/// Finds the channel picked in the channel select of the poll creation view.
+fn selected_channel(values: &Value) -> Option<String> {
+    values.as_object()?.values().find_map(|block| {
+        block
+            .get(CHANNEL_CHOOSE)?
+            .get("selected_channel")?
+            .as_str()
+            .map(str::to_owned)
+    })
+}
+
+/// Takes the value of the option picked in the select input `block_id` of a submitted view.
+fn selected_value(values: &mut Value, block_id: &str) -> Option<String> {
+    take_input_field(values, &Some(block_id.to_owned()), "selected_option")?
+        .get("value")?
+        .as_str()
+        .map(str::to_owned)
+}
+
+/// Takes the value typed into the input block `block_id` of a submitted view.
+fn take_input_value(values: &mut Value, block_id: &Option<String>) -> Option<Value> {
+    take_input_field(values, block_id, "value")
+}
+
+/// Takes `field` of the element in the input block `block_id`, e.g. `selected_date` of a picker.
+fn take_input_field(values: &mut Value, block_id: &Option<String>, field: &str) -> Option<Value> {
+    let block_id = block_id.as_ref()?;
+    values
+        .get_mut(block_id)?
+        .get_mut(block_id)?
+        .as_object_mut()?
+        .remove(field)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::application::{ballot_submission, poll_status_text, BallotSubmission};
+    use crate::fixtures::poll_view;
+    use crate::locale::{fill, workspace_messages};
+    use crate::slack_ui::{WITHDRAW_VOTE_INPUT, WITHDRAW_VOTE_YES};
+    use crate::ui_poll_view::{PollView, PollVisibility, VariantKey, EDIT_VOTE_ACTION_PREFIX};
+    use serde_json::{json, Value};
+
+    fn poll(visibility: PollVisibility, is_closed: bool) -> PollView {
+        let mut poll = PollView {
+            visibility,
+            is_closed,
+            ..poll_view(1)
+        };
+        poll.variants[0].votes = Some(3);
+        poll
+    }
+
+    #[test]
+    fn test_status_hides_counts_until_close() {
+        let text = workspace_messages();
+        let counts = fill(text.votes_count, &[&3]);
+        let hidden = poll_status_text(poll(PollVisibility::HiddenUntilClose, false));
+        assert!(hidden.contains(text.votes_hidden));
+        assert!(!hidden.contains(&counts));
+
+        let closed = poll_status_text(poll(PollVisibility::HiddenUntilClose, true));
+        assert!(closed.contains(&counts));
+        let counts_only = poll_status_text(poll(PollVisibility::CountsOnly, false));
+        assert!(counts_only.contains(&counts));
+    }
+
+    #[test]
+    fn test_withdraw_only_from_edit() {
+        let key = VariantKey::new(3, 40);
+        let withdraw = || {
+            json!({
+                WITHDRAW_VOTE_INPUT: {
+                    WITHDRAW_VOTE_INPUT: { "selected_option": { "value": WITHDRAW_VOTE_YES } }
+                }
+            })
+        };
+        let submission = |callback_id: String, mut values: Value| {
+            ballot_submission(&callback_id, &mut values).ok()
+        };
+        let edit_id = key.to_id_with_prefix(EDIT_VOTE_ACTION_PREFIX);
+
+        assert_eq!(
+            submission(key.to_action_id(), json!({})),
+            Some((key, BallotSubmission::New))
+        );
+        assert_eq!(
+            submission(edit_id.clone(), json!({})),
+            Some((key, BallotSubmission::Edit))
+        );
+        assert_eq!(
+            submission(edit_id, withdraw()),
+            Some((key, BallotSubmission::Withdraw))
+        );
+        // A first ballot has no withdraw input, a forged answer is ignored
+        assert_eq!(
+            submission(key.to_action_id(), withdraw()),
+            Some((key, BallotSubmission::New))
+        );
+        assert_eq!(submission("vote:x".to_owned(), json!({})), None);
     }
 }
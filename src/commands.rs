@@ -0,0 +1,238 @@
+use crate::error::AppError;
+use crate::locale::{fill, workspace_messages};
+use std::str::FromStr;
+
+/// Subcommand of `/poll`, e.g. `post 12`. Commands acting on a poll require its id,
+/// polls of different teams run at the same time.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PollCommand {
+    Create,
+    Post(i32),
+    /// Closes the poll and posts its report
+    Close(i32),
+    /// Posts the report of the poll without closing it
+    Report(i32),
+    Status(i32),
+    List,
+    Help,
+    Admin(AdminCommand),
+}
+
+/// Reason why the text of `/poll` is not a command.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CommandError {
+    Unknown(String),
+    /// Command written without the poll id
+    MissingPollId(String),
+    InvalidPollId(String),
+    UnexpectedArgument(String),
+    InvalidAdminCommand,
+}
+
+impl FromStr for PollCommand {
+    type Err = CommandError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let text = text.trim();
+        let (command, arguments) = match text.find(char::is_whitespace) {
+            Some(index) => (&text[..index], text[index..].trim()),
+            None => (text, ""),
+        };
+        let command = command.to_lowercase();
+        match command.as_str() {
+            "" | "help" => no_arguments(arguments, PollCommand::Help),
+            "create" => no_arguments(arguments, PollCommand::Create),
+            "list" => no_arguments(arguments, PollCommand::List),
+            "post" => poll_id(&command, arguments).map(PollCommand::Post),
+            "close" => poll_id(&command, arguments).map(PollCommand::Close),
+            "report" => poll_id(&command, arguments).map(PollCommand::Report),
+            "status" => poll_id(&command, arguments).map(PollCommand::Status),
+            "admin" => AdminCommand::from_str(arguments)
+                .map(PollCommand::Admin)
+                .map_err(|_| CommandError::InvalidAdminCommand),
+            _ => Err(CommandError::Unknown(command)),
+        }
+    }
+}
+
+impl From<CommandError> for AppError {
+    fn from(error: CommandError) -> Self {
+        let text = workspace_messages();
+        AppError::State(match error {
+            CommandError::Unknown(command) => fill(text.unknown_command, &[&command]),
+            CommandError::MissingPollId(command) => fill(text.missing_poll_id, &[&command]),
+            CommandError::InvalidPollId(id) => fill(text.invalid_poll_id, &[&id]),
+            CommandError::UnexpectedArgument(argument) => {
+                fill(text.unexpected_argument, &[&argument])
+            }
+            CommandError::InvalidAdminCommand => text.admin_usage.to_owned(),
+        })
+    }
+}
+
+fn no_arguments(arguments: &str, command: PollCommand) -> Result<PollCommand, CommandError> {
+    if arguments.is_empty() {
+        Ok(command)
+    } else {
+        Err(CommandError::UnexpectedArgument(arguments.to_owned()))
+    }
+}
+
+/// Poll id of `command`, written as `12` or `#12`.
+fn poll_id(command: &str, arguments: &str) -> Result<i32, CommandError> {
+    let mut words = arguments.split_whitespace();
+    let id = match words.next() {
+        Some(word) => match i32::from_str(word.trim_start_matches('#')) {
+            Ok(id) if id > 0 => id,
+            _ => return Err(CommandError::InvalidPollId(word.to_owned())),
+        },
+        None => return Err(CommandError::MissingPollId(command.to_owned())),
+    };
+    match words.next() {
+        Some(word) => Err(CommandError::UnexpectedArgument(word.to_owned())),
+        None => Ok(id),
+    }
+}
+
+/// Subcommand of `/poll admin`, e.g. `grant @user #channel`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AdminCommand {
+    /// Slack user id, channel or none for every channel
+    Grant(String, Option<String>),
+    /// Slack user id, channel or none for every channel
+    Revoke(String, Option<String>),
+    List,
+}
+
+impl FromStr for AdminCommand {
+    type Err = ();
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut words = text.split_whitespace();
+        let command = words.next().unwrap_or("list").to_lowercase();
+        if command == "list" {
+            return Ok(AdminCommand::List);
+        }
+        let user = words.next().and_then(user_id).ok_or(())?;
+        let channel = match words.next() {
+            Some(word) => Some(channel_id(word).ok_or(())?),
+            None => None,
+        };
+        match command.as_str() {
+            "grant" => Ok(AdminCommand::Grant(user, channel)),
+            "revoke" => Ok(AdminCommand::Revoke(user, channel)),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Id of an escaped mention, `<@U024BE7LH|bob>`, or the bare id.
+fn user_id(word: &str) -> Option<String> {
+    escaped_id(word, "<@").or_else(|| bare_id(word, &['U', 'W']))
+}
+
+/// Id of an escaped channel link, `<#C024BE7LR|general>`, or the bare id.
+fn channel_id(word: &str) -> Option<String> {
+    escaped_id(word, "<#").or_else(|| bare_id(word, &['C', 'G']))
+}
+
+fn escaped_id(word: &str, prefix: &str) -> Option<String> {
+    let id = word.strip_prefix(prefix)?.strip_suffix('>')?;
+    let id = id.split('|').next()?;
+    if id.is_empty() {
+        None
+    } else {
+        Some(id.to_owned())
+    }
+}
+
+fn bare_id(word: &str, prefixes: &[char]) -> Option<String> {
+    if word.starts_with(prefixes) && word.chars().all(|c| c.is_ascii_alphanumeric()) {
+        Some(word.to_owned())
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::commands::{AdminCommand, CommandError, PollCommand};
+    use std::str::FromStr;
+
+    #[test]
+    fn test_poll_commands() {
+        assert_eq!(PollCommand::from_str(""), Ok(PollCommand::Help));
+        assert_eq!(PollCommand::from_str(" Create "), Ok(PollCommand::Create));
+        assert_eq!(PollCommand::from_str("post 7"), Ok(PollCommand::Post(7)));
+        assert_eq!(
+            PollCommand::from_str("close #12"),
+            Ok(PollCommand::Close(12))
+        );
+        assert_eq!(
+            PollCommand::from_str("status 3"),
+            Ok(PollCommand::Status(3))
+        );
+        assert_eq!(
+            PollCommand::from_str("admin grant U024BE7LH"),
+            Ok(PollCommand::Admin(AdminCommand::Grant(
+                "U024BE7LH".to_owned(),
+                None
+            )))
+        );
+        assert_eq!(
+            PollCommand::from_str("close"),
+            Err(CommandError::MissingPollId("close".to_owned()))
+        );
+        assert_eq!(
+            PollCommand::from_str("close abc"),
+            Err(CommandError::InvalidPollId("abc".to_owned()))
+        );
+        assert_eq!(
+            PollCommand::from_str("Report "),
+            Err(CommandError::MissingPollId("report".to_owned()))
+        );
+        assert_eq!(
+            PollCommand::from_str("report twelve"),
+            Err(CommandError::InvalidPollId("twelve".to_owned()))
+        );
+        assert_eq!(
+            PollCommand::from_str("post 0"),
+            Err(CommandError::InvalidPollId("0".to_owned()))
+        );
+        assert_eq!(
+            PollCommand::from_str("post 1 2"),
+            Err(CommandError::UnexpectedArgument("2".to_owned()))
+        );
+        assert_eq!(
+            PollCommand::from_str("list all"),
+            Err(CommandError::UnexpectedArgument("all".to_owned()))
+        );
+        assert_eq!(
+            PollCommand::from_str("delete 1"),
+            Err(CommandError::Unknown("delete".to_owned()))
+        );
+        assert_eq!(
+            PollCommand::from_str("admin promote bob"),
+            Err(CommandError::InvalidAdminCommand)
+        );
+    }
+
+    #[test]
+    fn test_admin_commands() {
+        assert_eq!(AdminCommand::from_str(""), Ok(AdminCommand::List));
+        assert_eq!(AdminCommand::from_str("list"), Ok(AdminCommand::List));
+        assert_eq!(
+            AdminCommand::from_str("grant <@U024BE7LH|bob>"),
+            Ok(AdminCommand::Grant("U024BE7LH".to_owned(), None))
+        );
+        assert_eq!(
+            AdminCommand::from_str("revoke U024BE7LH <#C024BE7LR|general>"),
+            Ok(AdminCommand::Revoke(
+                "U024BE7LH".to_owned(),
+                Some("C024BE7LR".to_owned())
+            ))
+        );
+        assert!(AdminCommand::from_str("grant bob").is_err());
+        assert!(AdminCommand::from_str("promote <@U024BE7LH>").is_err());
+    }
+}
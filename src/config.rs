@@ -0,0 +1,358 @@
+use crate::data::{JudgeNormalization, ScoringMode};
+use crate::locale::Locale;
+use serde::Deserialize;
+use std::fmt;
+use std::str::FromStr;
+
+const DEFAULT_CONFIG_PATH: &str = "config.toml";
+
+/// Settings of the application, loaded once at startup by [`Config::load`].
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub slack: SlackConfig,
+    pub database: DatabaseConfig,
+    pub server: ServerConfig,
+    pub report: ReportConfig,
+    pub voting: VotingConfig,
+    /// Locale of the workspace, users with a known Slack locale get their own
+    pub locale: Locale,
+}
+
+#[derive(Clone, Debug)]
+pub struct SlackConfig {
+    pub api_key: String,
+    pub work_space: String,
+    pub signing_secret: String,
+    /// Slack id of the owner, an admin everywhere who grants the other roles
+    pub user_admin: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct DatabaseConfig {
+    pub url: String,
+    /// Number of database actors, each of them holds one connection at a time
+    pub pool_size: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct ServerConfig {
+    pub host: String,
+    pub port: u16,
+    pub workers: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct ReportConfig {
+    /// Variants with fewer voters are left out of the report
+    pub min_votes_count: i32,
+    /// Query replacing the built-in scoring, binds the poll id and `min_votes_count`
+    pub sql_counter: Option<String>,
+    pub scoring_mode: ScoringMode,
+    /// Rescaling of the scores of every judge, the report then shows both totals
+    pub judge_normalization: JudgeNormalization,
+}
+
+#[derive(Clone, Debug)]
+pub struct VotingConfig {
+    /// Key of the voter hashes of anonymous polls, they cannot be created without it
+    pub anonymous_key: Option<String>,
+}
+
+/// Every problem found in the configuration, reported together.
+#[derive(Debug)]
+pub struct ConfigError(pub Vec<String>);
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Invalid configuration:")?;
+        for error in &self.0 {
+            writeln!(f, "  - {}", error)?;
+        }
+        Ok(())
+    }
+}
+
+/// Layout of the TOML file, every value can be overridden by its environment variable.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigFile {
+    slack: SlackSection,
+    database: DatabaseSection,
+    server: ServerSection,
+    report: ReportSection,
+    voting: VotingSection,
+    locale: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct SlackSection {
+    api_key: Option<String>,
+    work_space: Option<String>,
+    signing_secret: Option<String>,
+    user_admin: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct DatabaseSection {
+    url: Option<String>,
+    pool_size: Option<usize>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ServerSection {
+    host: Option<String>,
+    port: Option<u16>,
+    workers: Option<usize>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ReportSection {
+    min_votes_count: Option<i32>,
+    sql_counter: Option<String>,
+    scoring_mode: Option<String>,
+    judge_normalization: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct VotingSection {
+    anonymous_key: Option<String>,
+}
+
+impl Config {
+    /// Reads the file from `CONFIG_PATH`, `config.toml` by default, and applies the environment.
+    /// A missing default file is not an error, then the environment alone is used.
+    pub fn load() -> Result<Config, ConfigError> {
+        dotenv::dotenv().ok();
+        let path = std::env::var("CONFIG_PATH").ok();
+        let file_path = path
+            .clone()
+            .unwrap_or_else(|| DEFAULT_CONFIG_PATH.to_owned());
+        let file = match std::fs::read_to_string(&file_path) {
+            Ok(file) => file,
+            Err(_) if path.is_none() => String::new(),
+            Err(e) => {
+                return Err(ConfigError(vec![format!(
+                    "cannot read {}: {}",
+                    file_path, e
+                )]))
+            }
+        };
+        Config::from_sources(&file, |name| std::env::var(name).ok())
+    }
+
+    fn from_sources<E>(file: &str, env: E) -> Result<Config, ConfigError>
+    where
+        E: Fn(&str) -> Option<String>,
+    {
+        let file: ConfigFile = toml::from_str(file)
+            .map_err(|e| ConfigError(vec![format!("cannot parse the config file: {}", e)]))?;
+        let env = |name: &str| env(name).filter(|value| !value.trim().is_empty());
+        let mut errors = vec![];
+        let slack = SlackConfig {
+            api_key: required(
+                &mut errors,
+                "API_KEY",
+                env("API_KEY").or(file.slack.api_key),
+            ),
+            work_space: required(
+                &mut errors,
+                "WORK_SPACE",
+                env("WORK_SPACE").or(file.slack.work_space),
+            ),
+            signing_secret: required(
+                &mut errors,
+                "SLACK_SIGNING_SECRET",
+                env("SLACK_SIGNING_SECRET").or(file.slack.signing_secret),
+            ),
+            user_admin: env("USER_ADMIN")
+                .or(file.slack.user_admin)
+                .unwrap_or_default(),
+        };
+        let database = DatabaseConfig {
+            url: required(
+                &mut errors,
+                "DATABASE_URL",
+                env("DATABASE_URL").or(file.database.url),
+            ),
+            pool_size: setting(
+                &mut errors,
+                "DATABASE_POOL_SIZE",
+                env("DATABASE_POOL_SIZE"),
+                file.database.pool_size.unwrap_or(4),
+                |size| *size > 0,
+            ),
+        };
+        let server = ServerConfig {
+            host: env("HOST")
+                .or(file.server.host)
+                .unwrap_or_else(|| "127.0.0.1".to_owned()),
+            port: setting(
+                &mut errors,
+                "PORT",
+                env("PORT"),
+                file.server.port.unwrap_or(8888),
+                |_| true,
+            ),
+            workers: setting(
+                &mut errors,
+                "WORKERS",
+                env("WORKERS"),
+                file.server.workers.unwrap_or_else(num_cpus::get),
+                |workers| *workers > 0,
+            ),
+        };
+        let report = ReportConfig {
+            min_votes_count: setting(
+                &mut errors,
+                "MIN_VOTES_COUNT",
+                env("MIN_VOTES_COUNT"),
+                file.report.min_votes_count.unwrap_or(0),
+                |count| *count >= 0,
+            ),
+            sql_counter: env("SQL_COUNTER")
+                .or(file.report.sql_counter)
+                .filter(|query| !query.trim().is_empty()),
+            scoring_mode: setting(
+                &mut errors,
+                "SCORING_MODE",
+                env("SCORING_MODE").or(file.report.scoring_mode),
+                ScoringMode::default(),
+                |_| true,
+            ),
+            judge_normalization: setting(
+                &mut errors,
+                "JUDGE_NORMALIZATION",
+                env("JUDGE_NORMALIZATION").or(file.report.judge_normalization),
+                JudgeNormalization::default(),
+                |_| true,
+            ),
+        };
+        let voting = VotingConfig {
+            anonymous_key: env("ANONYMOUS_BALLOT_KEY")
+                .or(file.voting.anonymous_key)
+                .filter(|key| !key.trim().is_empty()),
+        };
+        let locale = match env("LOCALE").or(file.locale) {
+            Some(locale) => Locale::from_slack(&locale).unwrap_or_else(|| {
+                errors.push(format!("LOCALE: unsupported locale {}", locale));
+                Locale::Ru
+            }),
+            None => Locale::Ru,
+        };
+        if errors.is_empty() {
+            Ok(Config {
+                slack,
+                database,
+                server,
+                report,
+                voting,
+                locale,
+            })
+        } else {
+            Err(ConfigError(errors))
+        }
+    }
+}
+
+fn required(errors: &mut Vec<String>, name: &str, value: Option<String>) -> String {
+    value.unwrap_or_else(|| {
+        errors.push(format!("{} must be set", name));
+        String::new()
+    })
+}
+
+/// Parses `value` when it is set, otherwise takes `current`, and checks the result.
+fn setting<T, V>(
+    errors: &mut Vec<String>,
+    name: &str,
+    value: Option<String>,
+    current: T,
+    valid: V,
+) -> T
+where
+    T: FromStr + fmt::Debug,
+    V: Fn(&T) -> bool,
+{
+    let setting = match value {
+        Some(value) => match T::from_str(value.trim()) {
+            Ok(setting) => setting,
+            Err(_) => {
+                errors.push(format!("{}: invalid value {}", name, value));
+                return current;
+            }
+        },
+        None => current,
+    };
+    if !valid(&setting) {
+        errors.push(format!("{}: invalid value {:?}", name, setting));
+    }
+    setting
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::Config;
+    use crate::data::{JudgeNormalization, ScoringMode};
+    use crate::locale::Locale;
+
+    const FILE: &str = r#"
+        locale = "en-US"
+
+        [slack]
+        api_key = "xoxb-file"
+        work_space = "team"
+        signing_secret = "secret"
+
+        [database]
+        url = "postgres://localhost/poll"
+
+        [report]
+        min_votes_count = 2
+        scoring_mode = "sum"
+        judge_normalization = "z_score"
+    "#;
+
+    #[test]
+    fn test_file_and_env_overrides() {
+        let config = Config::from_sources(FILE, |name| match name {
+            "API_KEY" => Some("xoxb-env".to_owned()),
+            "PORT" => Some("9000".to_owned()),
+            _ => None,
+        })
+        .unwrap();
+        assert_eq!(config.slack.api_key, "xoxb-env");
+        assert_eq!(config.slack.work_space, "team");
+        assert_eq!(config.server.host, "127.0.0.1");
+        assert_eq!(config.server.port, 9000);
+        assert_eq!(config.database.pool_size, 4);
+        assert_eq!(config.report.min_votes_count, 2);
+        assert_eq!(config.report.scoring_mode, ScoringMode::Sum);
+        assert_eq!(
+            config.report.judge_normalization,
+            JudgeNormalization::ZScore
+        );
+        assert_eq!(config.report.sql_counter, None);
+        assert_eq!(config.voting.anonymous_key, None);
+        assert_eq!(config.locale, Locale::En);
+    }
+
+    #[test]
+    fn test_invalid_values_are_reported_together() {
+        let errors = Config::from_sources("", |name| match name {
+            "PORT" => Some("eighty".to_owned()),
+            "DATABASE_POOL_SIZE" => Some("0".to_owned()),
+            _ => None,
+        })
+        .unwrap_err()
+        .0;
+        assert_eq!(errors.len(), 6);
+        assert!(errors.contains(&"API_KEY must be set".to_owned()));
+        assert!(errors.contains(&"PORT: invalid value eighty".to_owned()));
+        assert!(errors.contains(&"DATABASE_POOL_SIZE: invalid value 0".to_owned()));
+    }
+}
@@ -0,0 +1,87 @@
+use crate::data::{AdminScope, CheckAdmin, Database, GrantAdmin, ListAdmins, RevokeAdmin};
+use crate::error::AppError;
+use crate::schema::admins;
+use actix::Handler;
+use diesel::{
+    delete, insert_into, BoolExpressionMethods, ExpressionMethods, OptionalExtension, QueryDsl,
+    RunQueryDsl,
+};
+
+#[derive(Clone, Debug, Queryable, Identifiable, PartialEq)]
+#[table_name = "admins"]
+pub struct Admin {
+    pub id: i32,
+    pub user_slack_id: String,
+    /// None for the role in every channel
+    pub channel: Option<String>,
+}
+
+#[derive(Clone, Debug, Insertable, PartialEq)]
+#[table_name = "admins"]
+pub struct AdminWrite {
+    pub user_slack_id: String,
+    pub channel: Option<String>,
+}
+
+impl Handler<CheckAdmin> for Database {
+    type Result = Result<bool, AppError>;
+
+    fn handle(&mut self, msg: CheckAdmin, _: &mut Self::Context) -> Self::Result {
+        use crate::schema::admins::dsl::*;
+        let connection = self.0.get()?;
+        let roles = admins.filter(user_slack_id.eq(msg.0)).select(id);
+        match msg.1 {
+            AdminScope::Global => roles.filter(channel.is_null()).first::<i32>(&connection),
+            AdminScope::Channel(scope) => roles
+                .filter(channel.is_null().or(channel.eq(scope)))
+                .first::<i32>(&connection),
+            AdminScope::AnyChannel => roles.first::<i32>(&connection),
+        }
+        .optional()
+        .map(|role| role.is_some())
+        .map_err(|e| AppError::Database(format!("Cannot check admin role {}", e)))
+    }
+}
+
+impl Handler<GrantAdmin> for Database {
+    type Result = Result<bool, AppError>;
+
+    fn handle(&mut self, msg: GrantAdmin, _: &mut Self::Context) -> Self::Result {
+        let connection = self.0.get()?;
+        insert_into(admins::table)
+            .values(&msg.0)
+            .on_conflict_do_nothing()
+            .execute(&connection)
+            .map(|inserted| inserted > 0)
+            .map_err(|e| AppError::Database(format!("Cannot grant admin role {}", e)))
+    }
+}
+
+impl Handler<RevokeAdmin> for Database {
+    type Result = Result<bool, AppError>;
+
+    fn handle(&mut self, msg: RevokeAdmin, _: &mut Self::Context) -> Self::Result {
+        use crate::schema::admins::dsl::*;
+        let connection = self.0.get()?;
+        let roles = admins.filter(user_slack_id.eq(msg.0));
+        match msg.1 {
+            Some(scope) => delete(roles.filter(channel.eq(scope))).execute(&connection),
+            None => delete(roles.filter(channel.is_null())).execute(&connection),
+        }
+        .map(|deleted| deleted > 0)
+        .map_err(|e| AppError::Database(format!("Cannot revoke admin role {}", e)))
+    }
+}
+
+impl Handler<ListAdmins> for Database {
+    type Result = Result<Vec<Admin>, AppError>;
+
+    fn handle(&mut self, _: ListAdmins, _: &mut Self::Context) -> Self::Result {
+        use crate::schema::admins::dsl::*;
+        let connection = self.0.get()?;
+        admins
+            .order((channel.is_not_null(), channel, user_slack_id))
+            .load::<Admin>(&connection)
+            .map_err(|e| AppError::Database(format!("Cannot read admins {}", e)))
+    }
+}
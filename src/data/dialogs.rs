@@ -1,11 +1,14 @@
-use crate::data::{Database, PollViewSource, ReadDialogVariantsForLastDay};
+use crate::data::{Database, PollViewSource, ReadVoterCriteria};
+use crate::error::AppError;
 use crate::schema::poll::dsl::poll;
-use crate::schema::dialog_variants;
+use crate::schema::{dialog_variants, poll_jury};
+use crate::ui_poll_view::Track;
 use actix::{Actor, Handler};
 use diesel::{
     insert_into, r2d2, update, BelongingToDsl, ExpressionMethods, Identifiable, Insertable,
-    PgConnection, QueryDsl, Queryable, RunQueryDsl,
+    OptionalExtension, PgConnection, QueryDsl, Queryable, RunQueryDsl,
 };
+use std::str::FromStr;
 
 #[derive(Clone, Debug, Queryable, Associations, Identifiable, PartialEq)]
 #[belongs_to(PollViewSource, foreign_key = "day_id")]
@@ -15,6 +18,16 @@ pub struct DialogVariant {
     pub day_id: i32,
     pub variant_text: String,
     pub max_score: i32,
+    /// Relative weight in the weighted report
+    pub weight: i32,
+    /// `Track` of the voters scoring the criterion
+    pub track: String,
+}
+
+impl DialogVariant {
+    pub fn track(&self) -> Track {
+        Track::from_str(&self.track).unwrap_or_default()
+    }
 }
 
 #[derive(Clone, Debug, Insertable, PartialEq)]
@@ -23,21 +36,40 @@ pub struct DialogVariantWrite {
     pub day_id: i32,
     pub variant_text: String,
     pub max_score: i32,
+    pub weight: i32,
+    pub track: String,
+}
+
+#[derive(Clone, Debug, Insertable, PartialEq)]
+#[table_name = "poll_jury"]
+pub struct JuryMemberWrite {
+    pub poll_id: i32,
+    pub user_slack_id: String,
 }
 
-impl Handler<ReadDialogVariantsForLastDay> for Database {
-    type Result = Result<Vec<DialogVariant>, ()>;
+impl Handler<ReadVoterCriteria> for Database {
+    type Result = Result<Vec<DialogVariant>, AppError>;
 
-    fn handle(&mut self, _: ReadDialogVariantsForLastDay, _: &mut Self::Context) -> Self::Result {
-        use crate::schema::poll;
-        use crate::schema::dialog_variants::dsl::*;
-        let connection = self.0.get().unwrap();
-        let current_day = poll::table
-            .order(poll::id.desc())
-            .first::<PollViewSource>(&connection)
-            .expect("Cannot load poll");
-        DialogVariant::belonging_to(&current_day)
+    fn handle(&mut self, msg: ReadVoterCriteria, _: &mut Self::Context) -> Self::Result {
+        let connection = self.0.get()?;
+        let is_jury = poll_jury::table
+            .filter(poll_jury::poll_id.eq(msg.0))
+            .filter(poll_jury::user_slack_id.eq(&msg.1))
+            .select(poll_jury::id)
+            .first::<i32>(&connection)
+            .optional()
+            .map_err(|e| AppError::Database(format!("Cannot read poll jury {}", e)))?
+            .is_some();
+        let track = if is_jury {
+            Track::Jury
+        } else {
+            Track::Audience
+        };
+        dialog_variants::table
+            .filter(dialog_variants::day_id.eq(msg.0))
+            .filter(dialog_variants::track.eq(track.as_str()))
+            .order(dialog_variants::id)
             .load::<DialogVariant>(&connection)
-            .map_err(|e| println!("Cannot find dialog variants {}", e))
+            .map_err(|e| AppError::Database(format!("Cannot find dialog variants {}", e)))
     }
 }
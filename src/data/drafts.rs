@@ -0,0 +1,184 @@
+use crate::data::{
+    Database, DeleteStaleDrafts, ReadPollDraft, SaveDraftChannel, SaveDraftDialogVariants,
+    TakePollDraft,
+};
+use crate::error::AppError;
+use crate::locale::workspace_messages;
+use crate::poll_state::PollData;
+use crate::schema::poll_drafts;
+use actix::Handler;
+use chrono::{Duration, Local, NaiveDateTime};
+use diesel::result::Error;
+use diesel::{
+    insert_into, BoolExpressionMethods, Connection, ExpressionMethods, OptionalExtension,
+    PgConnection, QueryDsl, RunQueryDsl,
+};
+
+#[derive(Clone, Debug, Queryable, Identifiable, PartialEq)]
+#[table_name = "poll_drafts"]
+pub struct PollDraft {
+    pub id: i32,
+    pub user_slack_id: String,
+    pub view_id: String,
+    pub draft: String,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Clone, Debug, Insertable, AsChangeset, PartialEq)]
+#[table_name = "poll_drafts"]
+pub struct PollDraftWrite {
+    pub user_slack_id: String,
+    pub view_id: String,
+    pub draft: String,
+    pub updated_at: NaiveDateTime,
+}
+
+/// Drafts not changed for this long are removed by the voting scheduler.
+pub fn draft_lifetime() -> Duration {
+    Duration::days(1)
+}
+
+/// A missing draft is a new one. A draft that cannot be read, e.g. one written before
+/// `PollData` changed, is expired and the poll has to be created again.
+fn parse_draft(draft: Option<PollDraft>) -> Result<PollData, AppError> {
+    match draft {
+        Some(draft) => serde_json::from_str::<PollData>(&draft.draft).map_err(|e| {
+            println!("Cannot parse poll draft {} {}", draft.id, e);
+            AppError::State(workspace_messages().draft_expired.to_owned())
+        }),
+        None => Ok(PollData::default()),
+    }
+}
+
+/// Loads the draft of the author under a row lock, applies `change` and stores it back.
+fn update_draft<F>(
+    connection: &PgConnection,
+    author: String,
+    view: String,
+    change: F,
+) -> Result<(), AppError>
+where
+    F: FnOnce(&mut PollData),
+{
+    use crate::schema::poll_drafts::dsl::*;
+    let save_error = |e: Error| AppError::Database(format!("Cannot save poll draft {}", e));
+    connection.transaction::<_, AppError, _>(|| {
+        let existing = poll_drafts
+            .filter(user_slack_id.eq(&author).and(view_id.eq(&view)))
+            .for_update()
+            .first::<PollDraft>(connection)
+            .optional()
+            .map_err(save_error)?;
+        let mut data = parse_draft(existing)?;
+        change(&mut data);
+        let write = PollDraftWrite {
+            user_slack_id: author.clone(),
+            view_id: view.clone(),
+            draft: serde_json::to_string(&data).unwrap_or_default(),
+            updated_at: Local::now().naive_local(),
+        };
+        insert_into(poll_drafts)
+            .values(&write)
+            .on_conflict((user_slack_id, view_id))
+            .do_update()
+            .set(&write)
+            .execute(connection)
+            .map(|_| ())
+            .map_err(save_error)
+    })
+}
+
+impl Handler<SaveDraftChannel> for Database {
+    type Result = Result<(), AppError>;
+
+    fn handle(&mut self, msg: SaveDraftChannel, _: &mut Self::Context) -> Self::Result {
+        let connection = self.0.get()?;
+        let channel = msg.2;
+        update_draft(&connection, msg.0, msg.1, move |draft| {
+            draft.poll_channel = channel
+        })
+    }
+}
+
+impl Handler<SaveDraftDialogVariants> for Database {
+    type Result = Result<(), AppError>;
+
+    fn handle(&mut self, msg: SaveDraftDialogVariants, _: &mut Self::Context) -> Self::Result {
+        let connection = self.0.get()?;
+        let variants = msg.2;
+        update_draft(&connection, msg.0, msg.1, move |draft| {
+            draft.dialog_variants = variants
+        })
+    }
+}
+
+impl Handler<TakePollDraft> for Database {
+    type Result = Result<PollData, AppError>;
+
+    fn handle(&mut self, msg: TakePollDraft, _: &mut Self::Context) -> Self::Result {
+        use crate::schema::poll_drafts::dsl::*;
+        let connection = self.0.get()?;
+        diesel::delete(poll_drafts.filter(user_slack_id.eq(&msg.0).and(view_id.eq(&msg.1))))
+            .get_result::<PollDraft>(&connection)
+            .optional()
+            .map_err(|e| AppError::Database(format!("Cannot take poll draft {}", e)))
+            .and_then(parse_draft)
+    }
+}
+
+impl Handler<ReadPollDraft> for Database {
+    type Result = Result<PollData, AppError>;
+
+    fn handle(&mut self, msg: ReadPollDraft, _: &mut Self::Context) -> Self::Result {
+        use crate::schema::poll_drafts::dsl::*;
+        let connection = self.0.get()?;
+        poll_drafts
+            .filter(user_slack_id.eq(&msg.0).and(view_id.eq(&msg.1)))
+            .first::<PollDraft>(&connection)
+            .optional()
+            .map_err(|e| AppError::Database(format!("Cannot read poll draft {}", e)))
+            .and_then(parse_draft)
+    }
+}
+
+impl Handler<DeleteStaleDrafts> for Database {
+    type Result = Result<usize, AppError>;
+
+    fn handle(&mut self, _: DeleteStaleDrafts, _: &mut Self::Context) -> Self::Result {
+        use crate::schema::poll_drafts::dsl::*;
+        let connection = self.0.get()?;
+        let stale = Local::now().naive_local() - draft_lifetime();
+        diesel::delete(poll_drafts.filter(updated_at.lt(stale)))
+            .execute(&connection)
+            .map_err(|e| AppError::Database(format!("Cannot delete stale poll drafts {}", e)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::data::drafts::{parse_draft, PollDraft};
+    use crate::error::AppError;
+    use crate::locale::workspace_messages;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn test_unreadable_draft() {
+        let draft = |text: &str| PollDraft {
+            id: 1,
+            user_slack_id: "U061F7AUR".to_owned(),
+            view_id: "V0PKB1ZFV".to_owned(),
+            draft: text.to_owned(),
+            updated_at: NaiveDate::from_ymd(2026, 10, 18).and_hms(10, 0, 0),
+        };
+        assert!(parse_draft(None).unwrap().dialog_variants.is_empty());
+        let saved = parse_draft(Some(draft(r#"{"poll_channel":"C024BE7LR"}"#)));
+        assert_eq!(saved.unwrap().poll_channel, "C024BE7LR");
+
+        match parse_draft(Some(draft("{\"poll_channel\":"))) {
+            Err(AppError::State(message)) => {
+                assert_eq!(message, workspace_messages().draft_expired)
+            }
+            _ => panic!("An unreadable draft is expired"),
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use crate::data::{
+    Database, DialogVariant, PollViewSource, ReadHome, SingleVariantSource, VotesResult,
+};
+use crate::error::AppError;
+use crate::schema::{dialog_variants, poll, poll_messages, poll_variant, votes_results};
+use crate::timezone::now_utc;
+use actix::Handler;
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+use std::collections::HashMap;
+
+/// Everything shown on the App Home tab of one user.
+#[derive(Clone, Debug, Default)]
+pub struct HomeData {
+    /// Polls which are not closed yet, newest first
+    pub polls: Vec<PollViewSource>,
+    /// Poll id, ts of the first message of the posted polls
+    pub messages: HashMap<i32, String>,
+    /// Variants of these polls scored by the user
+    pub ballots: Vec<HomeBallot>,
+    /// Variants of these polls whose voting has not started yet, soonest first
+    pub upcoming: Vec<SingleVariantSource>,
+}
+
+#[derive(Clone, Debug)]
+pub struct HomeBallot {
+    pub variant: SingleVariantSource,
+    /// Criterion, max score, score
+    pub scores: Vec<(String, i32, i32)>,
+}
+
+impl Handler<ReadHome> for Database {
+    type Result = Result<HomeData, AppError>;
+
+    fn handle(&mut self, msg: ReadHome, _: &mut Self::Context) -> Self::Result {
+        let conn = &self.0.get()?;
+        let polls = poll::table
+            .filter(poll::is_closed.eq(false))
+            .order(poll::id.desc())
+            .load::<PollViewSource>(conn)
+            .map_err(|e| AppError::Database(format!("Cannot load open polls {}", e)))?;
+        let poll_ids = polls.iter().map(|poll| poll.id).collect::<Vec<_>>();
+        let messages = poll_messages::table
+            .filter(poll_messages::poll_id.eq_any(&poll_ids))
+            .filter(poll_messages::page.eq(0))
+            .select((poll_messages::poll_id, poll_messages::ts))
+            .load::<(i32, String)>(conn)
+            .map_err(|e| AppError::Database(format!("Cannot load poll messages {}", e)))?
+            .into_iter()
+            .collect();
+        let votes = votes_results::table
+            .filter(votes_results::user_id.eq(msg.0))
+            .filter(votes_results::day_id.eq_any(&poll_ids))
+            .order(votes_results::id)
+            .load::<VotesResult>(conn)
+            .map_err(|e| AppError::Database(format!("Cannot load user votes {}", e)))?;
+        let variant_ids = votes
+            .iter()
+            .map(|vote| vote.poll_variant_id)
+            .collect::<Vec<_>>();
+        let criterion_ids = votes
+            .iter()
+            .map(|vote| vote.dialog_variant_id)
+            .collect::<Vec<_>>();
+        let variants = poll_variant::table
+            .filter(poll_variant::id.eq_any(&variant_ids))
+            .order((poll_variant::day_id.desc(), poll_variant::id))
+            .load::<SingleVariantSource>(conn)
+            .map_err(|e| AppError::Database(format!("Cannot load voted variants {}", e)))?;
+        let criteria = dialog_variants::table
+            .filter(dialog_variants::id.eq_any(&criterion_ids))
+            .load::<DialogVariant>(conn)
+            .map_err(|e| AppError::Database(format!("Cannot load criteria {}", e)))?;
+        let upcoming = poll_variant::table
+            .filter(poll_variant::day_id.eq_any(&poll_ids))
+            .filter(poll_variant::start_date.gt(now_utc()))
+            .order(poll_variant::start_date)
+            .load::<SingleVariantSource>(conn)
+            .map_err(|e| AppError::Database(format!("Cannot load upcoming variants {}", e)))?;
+        let ballots = variants
+            .into_iter()
+            .map(|variant| HomeBallot {
+                scores: votes
+                    .iter()
+                    .filter(|vote| vote.poll_variant_id == variant.id)
+                    .filter_map(|vote| {
+                        criteria
+                            .iter()
+                            .find(|criterion| criterion.id == vote.dialog_variant_id)
+                            .map(|criterion| {
+                                (
+                                    criterion.variant_text.clone(),
+                                    criterion.max_score,
+                                    vote.score,
+                                )
+                            })
+                    })
+                    .collect(),
+                variant,
+            })
+            .collect();
+        Ok(HomeData {
+            polls,
+            messages,
+            ballots,
+            upcoming,
+        })
+    }
+}
@@ -1,15 +1,17 @@
+use crate::config::{Config, ReportConfig};
 use crate::data::{
-    ChannelUser, DialogVariant, PollReportSource, Pool, SingleVariantSource, VotesResult,
+    Admin, AdminWrite, ChannelUser, ChannelUserWrite, DialogVariant, HomeData, PollSummary, Pool,
+    ReportRanking, SingleVariantSource, VotesResult,
 };
+use crate::error::AppError;
 use crate::poll_state::PollData;
-use crate::ui_poll_view::{PollReport, PollView, SingleVariant};
+use crate::ui_poll_view::{DialogViewVariant, PollReport, PollView, SingleVariant};
 use actix::{Actor, Addr, Message, SyncArbiter, SyncContext};
 use diesel::r2d2::ConnectionManager;
 use diesel::{r2d2, PgConnection};
-use dotenv::dotenv;
-use std::env;
 
-pub struct Database(pub(crate) Pool);
+/// Database actor with the report settings of the configuration.
+pub struct Database(pub(crate) Pool, pub(crate) ReportConfig);
 
 impl Actor for Database {
     type Context = SyncContext<Self>;
@@ -17,85 +19,223 @@ impl Actor for Database {
 
 pub struct ReadPoll(pub i32);
 
-pub struct ReadLastPoll;
+/// Channel user id, whose App Home tab is shown
+pub struct ReadHome(pub i32);
+
+/// Most recent polls, newest first
+pub struct ListPolls(pub i64);
 
 pub struct FindUser(pub String);
 
-pub struct WriteUser(pub String, pub String);
+pub struct WriteUser(pub ChannelUserWrite);
+
+/// A complete ballot of one user for one variant, written in a single transaction.
+/// With `replace` the existing scores are overwritten, otherwise a repeated ballot is rejected.
+pub struct WriteBallot {
+    pub user_id: i32,
+    pub poll_id: i32,
+    pub variant_id: i32,
+    /// Dialog variant id, score
+    pub scores: Vec<(i32, i32)>,
+    pub replace: bool,
+    /// `voter_hash` of the user, stored instead of the user in anonymous polls
+    pub voter_hash: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BallotWrite {
+    Written,
+    AlreadyVoted,
+    /// The voter already has a ballot in the anonymous poll, it cannot be edited or withdrawn
+    AnonymousAlreadyVoted,
+}
 
-pub struct WriteVotes(pub i32, pub i32, pub i32, pub i32, pub i32);
+/// User id, poll id, variant id
+pub struct WithdrawVotes(pub i32, pub i32, pub i32);
 
-pub struct ReadVotesForCurrentDay;
+pub struct ReadVotesForPoll(pub i32);
 
-pub struct ReadPollVariant(pub i32);
+/// Poll id, variant id
+pub struct ReadPollVariant(pub i32, pub i32);
 
-pub struct ReadVotesForCurrentUser(pub String);
+/// Poll id, slack user id
+pub struct ReadVotesForCurrentUser(pub i32, pub String);
 
-pub struct ReadDialogVariantsForLastDay;
+/// Criteria of the track of the user in the poll: poll id, user slack id
+pub struct ReadVoterCriteria(pub i32, pub String);
+
+/// Whether the voter already scored the variant of an anonymous poll: variant id, voter hash
+pub struct HasAnonymousBallot(pub i32, pub String);
 
 pub struct WriteNewPoll(pub PollData);
 
-pub struct UpdatePollTime(pub String);
+/// Poll id, ts of every posted message of the poll in page order
+pub struct SavePollMessages(pub i32, pub Vec<String>);
+
+pub struct GetPollReport(pub i32);
+
+pub struct ClosePoll(pub i32);
+
+/// Author slack id, root view id, channel
+pub struct SaveDraftChannel(pub String, pub String, pub String);
+
+/// Author slack id, root view id, criteria
+pub struct SaveDraftDialogVariants(pub String, pub String, pub Vec<DialogViewVariant>);
+
+/// Author slack id, root view id. Removes the draft and returns its content.
+pub struct TakePollDraft(pub String, pub String);
+
+/// Author and view id, the draft stays stored
+pub struct ReadPollDraft(pub String, pub String);
+
+/// Removes the drafts not changed for `draft_lifetime`, returns their number
+pub struct DeleteStaleDrafts;
+
+/// Variants whose voting window has ended and whose poll message is not refreshed yet:
+/// poll id, variant id
+pub struct ReadEndedVariants;
 
-pub struct GetPollReport;
+/// Variant ids whose poll message shows the end of voting
+pub struct FinishVariants(pub Vec<i32>);
+
+/// Where an admin role is required.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AdminScope {
+    /// Role in every channel
+    Global,
+    /// Role in the channel or in every channel
+    Channel(String),
+    /// Role in at least one channel
+    AnyChannel,
+}
+
+/// Slack user id, scope. Tells whether the user has the role.
+pub struct CheckAdmin(pub String, pub AdminScope);
+
+/// Returns false when the role was already granted.
+pub struct GrantAdmin(pub AdminWrite);
+
+/// Slack user id, channel or none for the global role. Returns false when there was no such role.
+pub struct RevokeAdmin(pub String, pub Option<String>);
+
+pub struct ListAdmins;
 
 impl Message for WriteNewPoll {
-    type Result = Result<(), ()>;
+    type Result = Result<i32, AppError>;
 }
 
-impl Message for UpdatePollTime {
-    type Result = Result<(), ()>;
+impl Message for SavePollMessages {
+    type Result = Result<(), AppError>;
 }
 
 impl Message for FindUser {
-    type Result = Result<ChannelUser, ()>;
+    type Result = Result<ChannelUser, AppError>;
 }
 
 impl Message for WriteUser {
-    type Result = Result<ChannelUser, ()>;
+    type Result = Result<ChannelUser, AppError>;
 }
 
 impl Message for ReadPoll {
-    type Result = Result<PollView, ()>;
+    type Result = Result<PollView, AppError>;
 }
 
-impl Message for ReadLastPoll {
-    type Result = Result<PollView, ()>;
+impl Message for ReadHome {
+    type Result = Result<HomeData, AppError>;
+}
+
+impl Message for ListPolls {
+    type Result = Result<Vec<PollSummary>, AppError>;
 }
 
 impl Message for ReadPollVariant {
-    type Result = Result<SingleVariantSource, ()>;
+    type Result = Result<SingleVariantSource, AppError>;
+}
+
+impl Message for WriteBallot {
+    type Result = Result<BallotWrite, AppError>;
+}
+
+impl Message for HasAnonymousBallot {
+    type Result = Result<bool, AppError>;
 }
 
-impl Message for WriteVotes {
-    type Result = Result<(), ()>;
+impl Message for WithdrawVotes {
+    type Result = Result<usize, AppError>;
 }
 
-impl Message for ReadVotesForCurrentDay {
-    type Result = Result<Vec<Vec<VotesResult>>, ()>;
+impl Message for ReadVotesForPoll {
+    type Result = Result<Vec<Vec<VotesResult>>, AppError>;
 }
 
 impl Message for ReadVotesForCurrentUser {
-    type Result = Result<Vec<VotesResult>, ()>;
+    type Result = Result<Vec<VotesResult>, AppError>;
 }
 
-impl Message for ReadDialogVariantsForLastDay {
-    type Result = Result<Vec<DialogVariant>, ()>;
+impl Message for ReadVoterCriteria {
+    type Result = Result<Vec<DialogVariant>, AppError>;
 }
 
-impl Message for GetPollReport {
-    type Result = Result<Vec<PollReportSource>, ()>;
+impl Message for ReadEndedVariants {
+    type Result = Result<Vec<(i32, i32)>, AppError>;
+}
+
+impl Message for FinishVariants {
+    type Result = Result<usize, AppError>;
+}
+
+impl Message for SaveDraftChannel {
+    type Result = Result<(), AppError>;
+}
+
+impl Message for SaveDraftDialogVariants {
+    type Result = Result<(), AppError>;
+}
+
+impl Message for CheckAdmin {
+    type Result = Result<bool, AppError>;
+}
+
+impl Message for GrantAdmin {
+    type Result = Result<bool, AppError>;
+}
+
+impl Message for RevokeAdmin {
+    type Result = Result<bool, AppError>;
+}
+
+impl Message for ListAdmins {
+    type Result = Result<Vec<Admin>, AppError>;
+}
+
+impl Message for TakePollDraft {
+    type Result = Result<PollData, AppError>;
+}
+
+impl Message for DeleteStaleDrafts {
+    type Result = Result<usize, AppError>;
+}
+
+impl Message for ReadPollDraft {
+    type Result = Result<PollData, AppError>;
 }
 
-pub fn create_connection() -> Addr<Database> {
-    dotenv().ok();
+impl Message for ClosePoll {
+    type Result = Result<PollView, AppError>;
+}
 
-    let database_url =
-        env::var("DATABASE_URL").expect("DATABASE_URL env var doesnt exist and must be set");
+impl Message for GetPollReport {
+    type Result = Result<Vec<ReportRanking>, AppError>;
+}
 
-    let manager = ConnectionManager::<PgConnection>::new(database_url);
+pub fn create_connection(config: &Config) -> Addr<Database> {
+    let manager = ConnectionManager::<PgConnection>::new(config.database.url.as_str());
     let pool = r2d2::Pool::builder()
+        .max_size(config.database.pool_size as u32)
         .build(manager)
         .expect("Failed to create pool.");
-    SyncArbiter::start(4, move || Database(pool.clone()))
+    let report = config.report.clone();
+    SyncArbiter::start(config.database.pool_size, move || {
+        Database(pool.clone(), report.clone())
+    })
 }
@@ -1,12 +1,19 @@
+mod admins;
 mod dialogs;
+mod drafts;
+mod home;
 mod local_datasource;
 mod polls;
+mod scoring;
 mod users;
 mod votes;
 
 use diesel::r2d2::ConnectionManager;
 use diesel::{r2d2, PgConnection};
 
-pub use {dialogs::*, local_datasource::*, polls::*, users::*, votes::*};
+pub use {
+    admins::*, dialogs::*, drafts::*, home::*, local_datasource::*, polls::*, scoring::*, users::*,
+    votes::*,
+};
 
 pub type Pool = r2d2::Pool<ConnectionManager<PgConnection>>;
@@ -1,21 +1,28 @@
 use crate::data::{
-    ChannelUser, Database, DialogVariantWrite, GetPollReport, ReadLastPoll, ReadPoll,
-    ReadPollVariant, UpdatePollTime, VotesResult, WriteNewPoll,
+    ChannelUser, ClosePoll, Database, DialogVariantWrite, FinishVariants, JuryMemberWrite,
+    ListPolls, ReadEndedVariants, ReadPoll, ReadPollVariant, SavePollMessages, VotesResult,
+    WriteNewPoll,
 };
 use crate::diesel::query_dsl::methods::DistinctOnDsl;
 use crate::diesel::GroupedBy;
+use crate::error::AppError;
+use crate::locale::{fill, workspace_messages};
 use crate::schema::votes_results::all_columns;
-use crate::schema::{channel_users, poll, poll_variant, votes_results};
-use crate::ui_poll_view::{PollReport, PollView, SingleVariant};
+use crate::schema::{
+    anonymous_voters, channel_users, poll, poll_messages, poll_variant, votes_results,
+};
+use crate::timezone::now_utc;
+use crate::ui_poll_view::{PollReport, PollView, PollVisibility, SingleVariant, Voter};
+use crate::validation::check_jury_tracks;
 use actix::{Actor, Handler};
-use chrono::NaiveDateTime;
+use chrono::{DateTime, Local, TimeZone, Utc};
 use diesel::dsl::{avg, max};
 use diesel::expression::dsl::count;
 use diesel::pg::expression::array_comparison::any;
 use diesel::sql_types::{BigInt, Double, Float, Integer, Text};
 use diesel::{
-    insert_into, r2d2, select, sql_query, update, BelongingToDsl, ExpressionMethods, Identifiable,
-    Insertable, PgConnection, QueryDsl, Queryable, RunQueryDsl,
+    delete, insert_into, r2d2, select, sql_query, update, BelongingToDsl, Connection,
+    ExpressionMethods, Identifiable, Insertable, PgConnection, QueryDsl, Queryable, RunQueryDsl,
 };
 use std::str::FromStr;
 
@@ -27,8 +34,9 @@ pub struct SingleVariantSource {
     pub day_id: i32,
     pub title: Option<String>,
     pub variant: Option<String>,
-    pub start_date: NaiveDateTime,
-    pub end_date: Option<NaiveDateTime>,
+    pub start_date: DateTime<Utc>,
+    pub end_date: Option<DateTime<Utc>>,
+    pub is_finished: bool,
 }
 
 impl Default for SingleVariantSource {
@@ -38,20 +46,28 @@ impl Default for SingleVariantSource {
             day_id: 0,
             title: None,
             variant: None,
-            start_date: NaiveDateTime::from_timestamp(0, 0),
+            start_date: Utc.timestamp(0, 0),
             end_date: None,
+            is_finished: false,
         }
     }
 }
 
+impl SingleVariantSource {
+    /// Same condition as `ReadEndedVariants`, the window is over at its `end_date`.
+    pub fn is_voting_ended(&self, now: DateTime<Utc>) -> bool {
+        self.is_finished || self.end_date.map(|end| end <= now).unwrap_or(false)
+    }
+}
+
 #[derive(Clone, Debug, Insertable)]
 #[table_name = "poll_variant"]
 pub struct SingleVariantWrite {
     pub day_id: i32,
     pub title: String,
     pub variant: String,
-    pub start_date: NaiveDateTime,
-    pub end_date: Option<NaiveDateTime>,
+    pub start_date: DateTime<Utc>,
+    pub end_date: Option<DateTime<Utc>>,
 }
 
 #[derive(Clone, Debug, Insertable)]
@@ -59,7 +75,8 @@ pub struct SingleVariantWrite {
 pub struct PollViewWrite {
     pub channel: String,
     pub is_closed: bool,
-    pub time: Option<String>,
+    pub visibility: String,
+    pub is_anonymous: bool,
 }
 
 #[derive(Clone, Debug, Queryable, Identifiable, PartialEq)]
@@ -68,7 +85,34 @@ pub struct PollViewSource {
     pub id: i32,
     pub channel: String,
     pub is_closed: bool,
-    pub time: Option<String>,
+    /// `PollVisibility` of the poll
+    pub visibility: String,
+    /// Scores of anonymous polls are stored without their voters
+    pub is_anonymous: bool,
+}
+
+impl PollViewSource {
+    pub fn visibility(&self) -> PollVisibility {
+        PollVisibility::from_str(&self.visibility).unwrap_or_default()
+    }
+}
+
+/// Posted message of a poll, `page` 0 holds the header and the first variants.
+#[derive(Clone, Debug, Insertable, PartialEq)]
+#[table_name = "poll_messages"]
+pub struct PollMessageWrite {
+    pub poll_id: i32,
+    pub page: i32,
+    pub ts: String,
+}
+
+/// Poll with the number of its variants and of the users who voted in it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PollSummary {
+    pub poll: PollViewSource,
+    pub is_posted: bool,
+    pub variants: usize,
+    pub voters: usize,
 }
 
 impl Into<PollViewWrite> for &PollView {
@@ -76,7 +120,8 @@ impl Into<PollViewWrite> for &PollView {
         PollViewWrite {
             channel: self.channel.clone(),
             is_closed: false,
-            time: None,
+            visibility: self.visibility.as_str().to_owned(),
+            is_anonymous: self.is_anonymous,
         }
     }
 }
@@ -103,7 +148,7 @@ impl Into<Vec<SingleVariantWrite>> for &PollView {
                 title: element.title.clone(),
                 variant: element.variant.clone(),
                 start_date: element.start_date,
-                end_date: None,
+                end_date: element.end_date,
             })
             .collect()
     }
@@ -126,195 +171,331 @@ impl Into<PollView>
                 .1
                 .iter()
                 .map(|variant| {
-                    let images = votes
+                    let voters = votes
                         .iter()
-                        .filter(|element| element.poll_variant_id == variant.id);
-
+                        .filter(|vote| vote.poll_variant_id == variant.id)
+                        .filter_map(|vote| users.iter().find(|user| Some(user.id) == vote.user_id))
+                        .map(Voter::from)
+                        .collect::<Vec<_>>();
                     SingleVariant {
                         id: Some(variant.id),
                         title: variant.title.clone().unwrap_or(String::new()),
                         variant: variant.variant.clone().unwrap_or(Default::default()),
-                        images: images
-                            .clone()
-                            .flat_map(|votes| {
-                                users
-                                    .into_iter()
-                                    .filter(move |user| user.id == votes.user_id)
-                                    .map(|user| {
-                                        user.user_thumbnail.clone().unwrap_or(Default::default())
-                                    })
-                            })
-                            .collect(),
-                        votes: if images.clone().count() == 0 {
+                        votes: if voters.is_empty() {
                             None
                         } else {
-                            Some(images.count() as i32)
+                            Some(voters.len() as i32)
                         },
+                        voters,
                         start_date: variant.start_date,
+                        end_date: variant.end_date,
                     }
                 })
                 .collect(),
+            visibility: self.0.visibility(),
+            is_anonymous: self.0.is_anonymous,
             channel: self.0.channel,
             is_closed: self.0.is_closed,
-            time: self.0.time,
+            messages: vec![],
+        }
+    }
+}
+
+fn load_poll_view(conn: &PgConnection, poll: PollViewSource) -> Result<PollView, AppError> {
+    let variants = SingleVariantSource::belonging_to(&poll)
+        .order(poll_variant::id)
+        .load::<SingleVariantSource>(conn)
+        .map_err(|e| AppError::Database(format!("No variants for poll {} {}", poll.id, e)))?;
+    let users = channel_users::table
+        .load::<ChannelUser>(conn)
+        .unwrap_or(Default::default());
+    let votes = diesel::QueryDsl::distinct_on(
+        diesel::QueryDsl::filter(
+            VotesResult::belonging_to(&users),
+            votes_results::day_id.eq(poll.id),
+        ),
+        (votes_results::user_id, votes_results::poll_variant_id),
+    )
+    .load::<VotesResult>(conn)
+    .unwrap_or(Default::default());
+    let messages = poll_messages::table
+        .filter(poll_messages::poll_id.eq(poll.id))
+        .order(poll_messages::page)
+        .select(poll_messages::ts)
+        .load::<String>(conn)
+        .map_err(|e| AppError::Database(format!("Cannot load poll messages {}", e)))?;
+    let anonymous_votes = if poll.is_anonymous {
+        anonymous_voters::table
+            .filter(anonymous_voters::poll_id.eq(poll.id))
+            .select(anonymous_voters::poll_variant_id)
+            .load::<i32>(conn)
+            .map_err(|e| AppError::Database(format!("Cannot load anonymous voters {}", e)))?
+    } else {
+        vec![]
+    };
+    let mut poll_view: PollView = (poll, variants, users, votes).into();
+    poll_view.messages = messages;
+    if poll_view.is_anonymous {
+        // Anonymous scores have no users, each stored voter hash counts as a vote
+        for variant in poll_view.variants.iter_mut() {
+            let count = anonymous_votes
+                .iter()
+                .filter(|variant_id| Some(**variant_id) == variant.id)
+                .count();
+            variant.votes = if count == 0 { None } else { Some(count as i32) };
         }
     }
+    Ok(poll_view)
 }
 
 impl Handler<ReadPollVariant> for Database {
-    type Result = Result<SingleVariantSource, ()>;
+    type Result = Result<SingleVariantSource, AppError>;
 
     fn handle(&mut self, msg: ReadPollVariant, _: &mut Self::Context) -> Self::Result {
-        use crate::schema::poll;
         use crate::schema::poll_variant::dsl::*;
 
-        let conn = &self.0.get().unwrap();
-        let poll: PollViewSource =
-            diesel::QueryDsl::order(poll::table, poll::id.desc())
-                .first::<PollViewSource>(conn)
-                .expect("Cannot find last poll");
-        diesel::QueryDsl::filter(SingleVariantSource::belonging_to(&poll), id.eq(msg.0))
+        let conn = &self.0.get()?;
+        poll_variant
+            .filter(day_id.eq(msg.0))
+            .filter(id.eq(msg.1))
             .first::<SingleVariantSource>(conn)
-            .map_err(|e| println!("Cannot read variant for day {}", e))
+            .map_err(|e| AppError::or_missing(e, workspace_messages().variant_not_found))
     }
 }
 
-impl Handler<ReadLastPoll> for Database {
-    type Result = Result<PollView, ()>;
+impl Handler<ListPolls> for Database {
+    type Result = Result<Vec<PollSummary>, AppError>;
 
-    fn handle(&mut self, _: ReadLastPoll, _: &mut Self::Context) -> Self::Result {
-        use crate::schema::poll::*;
-        use crate::schema::poll_variant::dsl::*;
-
-        let conn = &self.0.get().unwrap();
-        let poll: PollViewSource =
-            diesel::QueryDsl::order(poll::table, poll::id.desc())
-                .first::<PollViewSource>(conn)
-                .expect("Cannot find last poll");
-        let variants = SingleVariantSource::belonging_to(&poll)
-            .load::<SingleVariantSource>(conn)
-            .expect("No variants for given id");
-        let users = channel_users::table
-            .load::<ChannelUser>(conn)
-            .unwrap_or(Default::default());
-        let votes = diesel::QueryDsl::distinct_on(
-            VotesResult::belonging_to(&users),
-            (votes_results::user_id, votes_results::poll_variant_id),
-        )
-        .load::<VotesResult>(conn)
-        .unwrap_or(Default::default());
-        println!("{:?},  --- {:?}", variants, votes);
-        Ok((poll, variants, users, votes).into())
+    fn handle(&mut self, msg: ListPolls, _: &mut Self::Context) -> Self::Result {
+        let conn = &self.0.get()?;
+        let polls = poll::table
+            .order(poll::id.desc())
+            .limit(msg.0)
+            .load::<PollViewSource>(conn)
+            .map_err(|e| AppError::Database(format!("Cannot load polls {}", e)))?;
+        let ids = polls.iter().map(|poll| poll.id).collect::<Vec<_>>();
+        let variants = poll_variant::table
+            .filter(poll_variant::day_id.eq_any(&ids))
+            .select(poll_variant::day_id)
+            .load::<i32>(conn)
+            .map_err(|e| AppError::Database(format!("Cannot load poll variants {}", e)))?;
+        let posted = poll_messages::table
+            .filter(poll_messages::poll_id.eq_any(&ids))
+            .filter(poll_messages::page.eq(0))
+            .select(poll_messages::poll_id)
+            .load::<i32>(conn)
+            .map_err(|e| AppError::Database(format!("Cannot load poll messages {}", e)))?;
+        let voters = votes_results::table
+            .filter(votes_results::day_id.eq_any(&ids))
+            .filter(votes_results::user_id.is_not_null())
+            .select((votes_results::day_id, votes_results::user_id))
+            .distinct()
+            .load::<(i32, Option<i32>)>(conn)
+            .map_err(|e| AppError::Database(format!("Cannot load poll voters {}", e)))?
+            .into_iter()
+            .map(|(day_id, _)| day_id);
+        // The voter hash is the same for every variant a user voted for in the poll
+        let anonymous_voters = anonymous_voters::table
+            .filter(anonymous_voters::poll_id.eq_any(&ids))
+            .select((anonymous_voters::poll_id, anonymous_voters::voter_hash))
+            .distinct()
+            .load::<(i32, String)>(conn)
+            .map_err(|e| AppError::Database(format!("Cannot load anonymous voters {}", e)))?
+            .into_iter()
+            .map(|(poll_id, _)| poll_id);
+        let voters = voters.chain(anonymous_voters).collect::<Vec<_>>();
+        Ok(polls
+            .into_iter()
+            .map(|poll| PollSummary {
+                is_posted: posted.contains(&poll.id),
+                variants: variants.iter().filter(|day_id| **day_id == poll.id).count(),
+                voters: voters.iter().filter(|day_id| **day_id == poll.id).count(),
+                poll,
+            })
+            .collect())
     }
 }
 
 impl Handler<ReadPoll> for Database {
-    type Result = Result<PollView, ()>;
+    type Result = Result<PollView, AppError>;
 
-    fn handle(&mut self, _: ReadPoll, _: &mut Self::Context) -> Self::Result {
-        use crate::schema::poll::*;
-        use crate::schema::poll_variant::dsl::*;
+    fn handle(&mut self, msg: ReadPoll, _: &mut Self::Context) -> Self::Result {
+        let conn = &self.0.get()?;
+        let poll: PollViewSource = poll::table
+            .find(msg.0)
+            .first::<PollViewSource>(conn)
+            .map_err(|e| AppError::or_missing(e, workspace_messages().poll_not_found))?;
+        load_poll_view(conn, poll)
+    }
+}
 
-        let conn = &self.0.get().unwrap();
-        let poll: PollViewSource =
-            diesel::QueryDsl::order(poll::table, poll::id.desc())
-                .first::<PollViewSource>(conn)
-                .expect("Cannot find last poll");
-        let variants = SingleVariantSource::belonging_to(&poll)
-            .load::<SingleVariantSource>(conn)
-            .expect("No days for given id");
-        let users = channel_users::table
-            .load::<ChannelUser>(conn)
-            .unwrap_or(Default::default());
-        let votes = diesel::QueryDsl::distinct_on(
-            VotesResult::belonging_to(&users),
-            votes_results::user_id,
-        )
-        .load::<VotesResult>(conn)
-        .unwrap_or(Default::default());
-        Ok((poll, variants, users, votes).into())
+impl Handler<SavePollMessages> for Database {
+    type Result = Result<(), AppError>;
+
+    fn handle(&mut self, msg: SavePollMessages, _: &mut Self::Context) -> Self::Result {
+        let conn = &self.0.get()?;
+        let messages = msg
+            .1
+            .into_iter()
+            .enumerate()
+            .map(|(page, ts)| PollMessageWrite {
+                poll_id: msg.0,
+                page: page as i32,
+                ts,
+            })
+            .collect::<Vec<_>>();
+        conn.transaction::<_, diesel::result::Error, _>(|| {
+            delete(poll_messages::table.filter(poll_messages::poll_id.eq(msg.0))).execute(conn)?;
+            insert_into(poll_messages::table)
+                .values(&messages)
+                .execute(conn)
+                .map(|_| ())
+        })
+        .map_err(|e| AppError::Database(format!("Cannot save poll messages {}", e)))
     }
 }
 
-impl Handler<UpdatePollTime> for Database {
-    type Result = Result<(), ()>;
-
-    fn handle(&mut self, msg: UpdatePollTime, _: &mut Self::Context) -> Self::Result {
-        use crate::schema::poll::*;
-        let conn = &self.0.get().unwrap();
-        let poll: PollViewSource =
-            diesel::QueryDsl::order(poll::table, poll::id.desc())
-                .first::<PollViewSource>(conn)
-                .expect("Cannot find last poll");
-        update(table.filter(id.eq(poll.id)))
-            .set(time.eq(Some(msg.0)))
-            .execute(conn)
-            .map(|_| ())
-            .map_err(|e| println!("Cannot update day time {}", e))
+impl Handler<ClosePoll> for Database {
+    type Result = Result<PollView, AppError>;
+
+    fn handle(&mut self, msg: ClosePoll, _: &mut Self::Context) -> Self::Result {
+        let conn = &self.0.get()?;
+        let poll = update(poll::table.find(msg.0))
+            .set(poll::is_closed.eq(true))
+            .get_result::<PollViewSource>(conn)
+            .map_err(|e| AppError::or_missing(e, workspace_messages().poll_not_found))?;
+        load_poll_view(conn, poll)
+    }
+}
+
+impl Handler<ReadEndedVariants> for Database {
+    type Result = Result<Vec<(i32, i32)>, AppError>;
+
+    fn handle(&mut self, _: ReadEndedVariants, _: &mut Self::Context) -> Self::Result {
+        use crate::schema::poll_variant::dsl::*;
+        let conn = &self.0.get()?;
+        poll_variant
+            .filter(end_date.le(now_utc()))
+            .filter(is_finished.eq(false))
+            .select((day_id, id))
+            .load::<(i32, i32)>(conn)
+            .map_err(|e| AppError::Database(format!("Cannot read ended variants {}", e)))
     }
 }
 
-impl Handler<GetPollReport> for Database {
-    type Result = Result<Vec<PollReportSource>, ()>;
-
-    fn handle(&mut self, msg: GetPollReport, _: &mut Self::Context) -> Self::Result {
-        let conn = &self.0.get().unwrap();
-        let limit = std::env::var("MIN_VOTES_COUNT").unwrap_or(Default::default());
-        let limit_num = i32::from_str(limit.as_str()).unwrap_or(0);
-        let poll: PollViewSource =
-            diesel::QueryDsl::order(poll::table, poll::id.desc())
-                .first::<PollViewSource>(conn)
-                .expect("Cannot find last poll");
-        sql_query(std::env::var("SQL_COUNTER").unwrap_or(String::default()))
-            .bind::<Integer, _>(poll.id)
-            .bind::<Integer, _>(limit_num)
-            .load::<PollReportSource>(conn)
-            .map_err(|e| println!("Cannot create report cause {}", e))
+impl Handler<FinishVariants> for Database {
+    type Result = Result<usize, AppError>;
+
+    fn handle(&mut self, msg: FinishVariants, _: &mut Self::Context) -> Self::Result {
+        use crate::schema::poll_variant::dsl::*;
+        let conn = &self.0.get()?;
+        update(poll_variant.filter(id.eq_any(msg.0)))
+            .set(is_finished.eq(true))
+            .execute(conn)
+            .map_err(|e| AppError::Database(format!("Cannot finish ended variants {}", e)))
     }
 }
 
 impl Handler<WriteNewPoll> for Database {
-    type Result = Result<(), ()>;
+    type Result = Result<i32, AppError>;
 
     fn handle(&mut self, msg: WriteNewPoll, ctx: &mut Self::Context) -> Self::Result {
         let poll_channel = msg.0.poll_channel;
+        let visibility = msg.0.visibility;
+        let is_anonymous = msg.0.is_anonymous;
+        let jury = msg.0.jury;
         let dialog_variants = msg.0.dialog_variants;
+        // Checked when the poll creation view is submitted, kept here for any other writer
+        check_jury_tracks(!jury.is_empty(), &dialog_variants).map_err(AppError::State)?;
         let poll_variants = msg.0.poll_variants;
-        let connection = &self.0.get().unwrap();
-        let poll = insert_into(crate::schema::poll::table)
-            .values(PollViewWrite {
-                channel: poll_channel,
-                is_closed: false,
-                time: None,
-            })
-            .get_result::<PollViewSource>(connection)
-            .expect("Cannot write poll");
-        let dialog_variants_write = dialog_variants
-            .into_iter()
-            .map(|e| DialogVariantWrite {
-                day_id: poll.id,
-                variant_text: e.variant_text,
-                max_score: e.max_score.last().unwrap(),
-            })
-            .collect::<Vec<_>>();
-        insert_into(crate::schema::dialog_variants::table)
-            .values(dialog_variants_write)
-            .execute(connection)
-            .expect("Cannot write dialog variants");
-        let poll_write_variants = poll_variants
-            .into_iter()
-            .map(|e| SingleVariantWrite {
-                day_id: poll.id,
-                title: e.title,
-                variant: e.variant,
-                start_date: e.start_date,
-                end_date: None,
-            })
-            .collect::<Vec<_>>();
-        insert_into(crate::schema::poll_variant::table)
-            .values(poll_write_variants)
-            .execute(connection)
-            .map_err(|e| println!("Cannot write poll variants {}", e))
-            .map(|_| ())
+        let connection = &self.0.get()?;
+        connection.transaction::<_, AppError, _>(|| {
+            let poll = insert_into(crate::schema::poll::table)
+                .values(PollViewWrite {
+                    channel: poll_channel,
+                    is_closed: false,
+                    visibility: visibility.as_str().to_owned(),
+                    is_anonymous,
+                })
+                .get_result::<PollViewSource>(connection)
+                .map_err(|e| AppError::Database(format!("Cannot write poll {}", e)))?;
+            let dialog_variants_write = dialog_variants
+                .into_iter()
+                .map(|e| {
+                    let max_score = e.max_score.last().ok_or_else(|| {
+                        AppError::State(fill(
+                            workspace_messages().criterion_without_scores,
+                            &[&e.variant_text],
+                        ))
+                    })?;
+                    Ok(DialogVariantWrite {
+                        day_id: poll.id,
+                        variant_text: e.variant_text,
+                        max_score,
+                        weight: e.weight,
+                        track: e.track.as_str().to_owned(),
+                    })
+                })
+                .collect::<Result<Vec<_>, AppError>>()?;
+            insert_into(crate::schema::dialog_variants::table)
+                .values(dialog_variants_write)
+                .execute(connection)
+                .map_err(|e| AppError::Database(format!("Cannot write dialog variants {}", e)))?;
+            let jury_write = jury
+                .into_iter()
+                .map(|user_slack_id| JuryMemberWrite {
+                    poll_id: poll.id,
+                    user_slack_id,
+                })
+                .collect::<Vec<_>>();
+            insert_into(crate::schema::poll_jury::table)
+                .values(jury_write)
+                .on_conflict_do_nothing()
+                .execute(connection)
+                .map_err(|e| AppError::Database(format!("Cannot write poll jury {}", e)))?;
+            let poll_write_variants = poll_variants
+                .into_iter()
+                .map(|e| SingleVariantWrite {
+                    day_id: poll.id,
+                    title: e.title,
+                    variant: e.variant,
+                    start_date: e.start_date,
+                    end_date: e.end_date,
+                })
+                .collect::<Vec<_>>();
+            insert_into(crate::schema::poll_variant::table)
+                .values(poll_write_variants)
+                .execute(connection)
+                .map_err(|e| AppError::Database(format!("Cannot write poll variants {}", e)))
+                .map(|_| poll.id)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::data::SingleVariantSource;
+    use chrono::{Duration, TimeZone, Utc};
+
+    #[test]
+    fn test_voting_end() {
+        let now = Utc.ymd(2026, 10, 18).and_hms(12, 0, 0);
+        let mut variant = SingleVariantSource {
+            start_date: now - Duration::hours(1),
+            ..Default::default()
+        };
+        assert!(!variant.is_voting_ended(now));
+
+        variant.end_date = Some(now + Duration::seconds(1));
+        assert!(!variant.is_voting_ended(now));
+        variant.end_date = Some(now);
+        assert!(variant.is_voting_ended(now));
+        variant.end_date = Some(now - Duration::seconds(1));
+        assert!(variant.is_voting_ended(now));
+
+        // A variant finished by the scheduler stays ended
+        variant.end_date = Some(now + Duration::hours(1));
+        variant.is_finished = true;
+        assert!(variant.is_voting_ended(now));
     }
 }
@@ -0,0 +1,628 @@
+use crate::data::{
+    Database, DialogVariant, GetPollReport, PollReportSource, PollViewSource, SingleVariantSource,
+    VotesResult,
+};
+use crate::error::AppError;
+use crate::locale::workspace_messages;
+use crate::schema::{dialog_variants, poll};
+use crate::ui_poll_view::Track;
+use actix::Handler;
+use diesel::sql_types::Integer;
+use diesel::{sql_query, BelongingToDsl, ExpressionMethods, QueryDsl, RunQueryDsl};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+
+/// How the scores of a single variant are aggregated into report points.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ScoringMode {
+    /// Sum of every score given to the variant.
+    Sum,
+    /// Average ballot, i.e. the sum of scores divided by the number of voters.
+    Mean,
+    /// Weighted average of the mean `score / max_score` of each criterion, scaled to 0..100.
+    NormalizedMean,
+}
+
+impl Default for ScoringMode {
+    fn default() -> Self {
+        ScoringMode::NormalizedMean
+    }
+}
+
+impl FromStr for ScoringMode {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "sum" => Ok(ScoringMode::Sum),
+            "mean" => Ok(ScoringMode::Mean),
+            "normalized" | "normalized_mean" | "weighted" => Ok(ScoringMode::NormalizedMean),
+            _ => Err(()),
+        }
+    }
+}
+
+/// How the scores of every judge are rescaled before they are aggregated, so lenient and
+/// strict judges weigh the same.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum JudgeNormalization {
+    Off,
+    /// Standard score of each criterion over the variants the judge rated.
+    ZScore,
+    /// Rank of each criterion score among the variants the judge rated, scaled to 0..100.
+    Rank,
+}
+
+impl Default for JudgeNormalization {
+    fn default() -> Self {
+        JudgeNormalization::Off
+    }
+}
+
+impl FromStr for JudgeNormalization {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "off" | "none" => Ok(JudgeNormalization::Off),
+            "z_score" | "zscore" => Ok(JudgeNormalization::ZScore),
+            "rank" => Ok(JudgeNormalization::Rank),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct VariantScore {
+    pub variant_id: i32,
+    pub title: String,
+    pub voters: usize,
+    pub score: f64,
+    /// Weighted mean of the normalized scores, see `normalize_judges`
+    pub normalized: Option<f64>,
+    /// Criteria scored for the variant, in the order of the poll criteria
+    pub criteria: Vec<CriterionScore>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CriterionScore {
+    pub criterion: String,
+    /// Weight of the criterion in percent of the total weight of the poll criteria
+    pub share: f64,
+    /// Mean score given by the voters of the variant
+    pub mean: f64,
+    pub max_score: i32,
+}
+
+/// Line of the report, the criteria breakdown is empty when `SQL_COUNTER` made the report.
+#[derive(Clone, Debug)]
+pub struct ReportEntry {
+    pub source: PollReportSource,
+    pub normalized: Option<f64>,
+    pub criteria: Vec<CriterionScore>,
+}
+
+/// Ranking of one voter group, without a track when `SQL_COUNTER` made the report.
+#[derive(Clone, Debug)]
+pub struct ReportRanking {
+    pub track: Option<Track>,
+    pub entries: Vec<ReportEntry>,
+}
+
+/// Scores every variant that got at least `min_votes` voters, best first.
+pub fn score_variants(
+    mode: ScoringMode,
+    variants: &[SingleVariantSource],
+    criteria: &[DialogVariant],
+    votes: &[VotesResult],
+    min_votes: usize,
+) -> Vec<VariantScore> {
+    let total_weight = criteria
+        .iter()
+        .map(|criterion| criterion.weight.max(1))
+        .sum::<i32>()
+        .max(1) as f64;
+    let mut scores = variants
+        .iter()
+        .filter_map(|variant| {
+            let ballots = votes
+                .iter()
+                .filter(|vote| vote.poll_variant_id == variant.id)
+                .collect::<Vec<_>>();
+            // Anonymous scores have no user, the ballot number tells their voters apart
+            let voters = ballots
+                .iter()
+                .map(|vote| (vote.user_id, vote.ballot))
+                .collect::<HashSet<_>>()
+                .len();
+            if voters == 0 || voters < min_votes {
+                return None;
+            }
+            let breakdown = criteria
+                .iter()
+                .filter_map(|criterion| {
+                    let given = ballots
+                        .iter()
+                        .filter(|vote| vote.dialog_variant_id == criterion.id)
+                        .map(|vote| vote.score as f64)
+                        .collect::<Vec<_>>();
+                    if given.is_empty() {
+                        return None;
+                    }
+                    Some(CriterionScore {
+                        criterion: criterion.variant_text.clone(),
+                        share: criterion.weight.max(1) as f64 / total_weight * 100.0,
+                        mean: given.iter().sum::<f64>() / given.len() as f64,
+                        max_score: criterion.max_score.max(1),
+                    })
+                })
+                .collect::<Vec<_>>();
+            let total = ballots.iter().map(|vote| vote.score as f64).sum::<f64>();
+            let score = match mode {
+                ScoringMode::Sum => total,
+                ScoringMode::Mean => total / voters as f64,
+                ScoringMode::NormalizedMean => {
+                    // Criteria nobody scored are left out of the weights
+                    let shares = breakdown.iter().map(|score| score.share).sum::<f64>();
+                    breakdown
+                        .iter()
+                        .map(|score| score.share * score.mean / score.max_score as f64)
+                        .sum::<f64>()
+                        / shares.max(std::f64::EPSILON)
+                        * 100.0
+                }
+            };
+            Some(VariantScore {
+                variant_id: variant.id,
+                title: variant.title.clone().unwrap_or_default(),
+                voters,
+                score,
+                normalized: None,
+                criteria: breakdown,
+            })
+        })
+        .collect::<Vec<_>>();
+    scores.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(Ordering::Equal));
+    scores
+}
+
+/// Normalized value of every named vote, keyed by vote index. The scores of a judge are
+/// rescaled per criterion over the variants the judge rated. Anonymous ballots cannot be
+/// linked across variants and stay out of the normalization.
+fn judge_values(method: JudgeNormalization, votes: &[VotesResult]) -> HashMap<usize, f64> {
+    let mut judges = HashMap::<(i32, i32), Vec<usize>>::new();
+    for (index, vote) in votes.iter().enumerate() {
+        if let Some(user_id) = vote.user_id {
+            judges
+                .entry((user_id, vote.dialog_variant_id))
+                .or_default()
+                .push(index);
+        }
+    }
+    let mut values = HashMap::new();
+    for indexes in judges.values() {
+        let scores = indexes
+            .iter()
+            .map(|index| votes[*index].score as f64)
+            .collect::<Vec<_>>();
+        let count = scores.len() as f64;
+        for (index, score) in indexes.iter().zip(&scores) {
+            let value = match method {
+                JudgeNormalization::Off => continue,
+                JudgeNormalization::ZScore => {
+                    let mean = scores.iter().sum::<f64>() / count;
+                    let deviation =
+                        (scores.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / count).sqrt();
+                    // A judge giving the same score everywhere has no preference
+                    if deviation > std::f64::EPSILON {
+                        (score - mean) / deviation
+                    } else {
+                        0.0
+                    }
+                }
+                JudgeNormalization::Rank => {
+                    // Ties share the mean of their ranks
+                    let below = scores.iter().filter(|s| *s < score).count() as f64;
+                    let equal = scores.iter().filter(|s| *s == score).count() as f64;
+                    if count > 1.0 {
+                        (below + (equal - 1.0) / 2.0) / (count - 1.0) * 100.0
+                    } else {
+                        50.0
+                    }
+                }
+            };
+            values.insert(*index, value);
+        }
+    }
+    values
+}
+
+/// Fills the normalized total of the scored variants and ranks them by it, the variants
+/// without named votes go last in the order of their raw score.
+pub fn normalize_judges(
+    method: JudgeNormalization,
+    criteria: &[DialogVariant],
+    votes: &[VotesResult],
+    scores: &mut [VariantScore],
+) {
+    if method == JudgeNormalization::Off {
+        return;
+    }
+    let values = judge_values(method, votes);
+    for score in scores.iter_mut() {
+        let (total, weights) = criteria
+            .iter()
+            .filter_map(|criterion| {
+                let given = votes
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, vote)| {
+                        vote.poll_variant_id == score.variant_id
+                            && vote.dialog_variant_id == criterion.id
+                    })
+                    .filter_map(|(index, _)| values.get(&index))
+                    .collect::<Vec<_>>();
+                if given.is_empty() {
+                    return None;
+                }
+                let weight = criterion.weight.max(1) as f64;
+                let mean = given.iter().copied().sum::<f64>() / given.len() as f64;
+                Some((weight * mean, weight))
+            })
+            .fold((0.0, 0.0), |(total, weights), (value, weight)| {
+                (total + value, weights + weight)
+            });
+        score.normalized = if weights > 0.0 {
+            Some(total / weights)
+        } else {
+            None
+        };
+    }
+    scores.sort_by(|a, b| {
+        b.normalized
+            .partial_cmp(&a.normalized)
+            .unwrap_or(Ordering::Equal)
+    });
+}
+
+/// Scores the variants separately for every track with criteria, each track only
+/// counts the scores given for its own criteria.
+pub fn rank_tracks(
+    mode: ScoringMode,
+    normalization: JudgeNormalization,
+    variants: &[SingleVariantSource],
+    criteria: &[DialogVariant],
+    votes: &[VotesResult],
+    min_votes: usize,
+) -> Vec<(Track, Vec<VariantScore>)> {
+    Track::ALL
+        .iter()
+        .filter_map(|track| {
+            let track_criteria = criteria
+                .iter()
+                .filter(|criterion| criterion.track() == *track)
+                .cloned()
+                .collect::<Vec<_>>();
+            if track_criteria.is_empty() {
+                return None;
+            }
+            let track_votes = votes
+                .iter()
+                .filter(|vote| {
+                    track_criteria
+                        .iter()
+                        .any(|criterion| criterion.id == vote.dialog_variant_id)
+                })
+                .cloned()
+                .collect::<Vec<_>>();
+            let mut scores =
+                score_variants(mode, variants, &track_criteria, &track_votes, min_votes);
+            normalize_judges(normalization, &track_criteria, &track_votes, &mut scores);
+            Some((*track, scores))
+        })
+        .collect()
+}
+
+impl Handler<GetPollReport> for Database {
+    type Result = Result<Vec<ReportRanking>, AppError>;
+
+    fn handle(&mut self, msg: GetPollReport, _: &mut Self::Context) -> Self::Result {
+        let conn = &self.0.get()?;
+        let limit_num = self.1.min_votes_count;
+        if let Some(custom_query) = self.1.sql_counter.clone() {
+            return sql_query(custom_query)
+                .bind::<Integer, _>(msg.0)
+                .bind::<Integer, _>(limit_num)
+                .load::<PollReportSource>(conn)
+                .map(|report| {
+                    let entries = report
+                        .into_iter()
+                        .map(|source| ReportEntry {
+                            source,
+                            normalized: None,
+                            criteria: vec![],
+                        })
+                        .collect::<Vec<_>>();
+                    if entries.is_empty() {
+                        vec![]
+                    } else {
+                        vec![ReportRanking {
+                            track: None,
+                            entries,
+                        }]
+                    }
+                })
+                .map_err(|e| AppError::Database(format!("Cannot create report cause {}", e)));
+        }
+        let mode = self.1.scoring_mode;
+        let poll = poll::table
+            .find(msg.0)
+            .first::<PollViewSource>(conn)
+            .map_err(|e| AppError::or_missing(e, workspace_messages().poll_not_found))?;
+        let variants = SingleVariantSource::belonging_to(&poll)
+            .load::<SingleVariantSource>(conn)
+            .map_err(|e| AppError::Database(format!("Cannot load variants for report {}", e)))?;
+        let criteria = DialogVariant::belonging_to(&poll)
+            .order(dialog_variants::id)
+            .load::<DialogVariant>(conn)
+            .map_err(|e| AppError::Database(format!("Cannot load criteria for report {}", e)))?;
+        let votes = VotesResult::belonging_to(&poll)
+            .load::<VotesResult>(conn)
+            .map_err(|e| AppError::Database(format!("Cannot load votes for report {}", e)))?;
+        Ok(rank_tracks(
+            mode,
+            self.1.judge_normalization,
+            &variants,
+            &criteria,
+            &votes,
+            limit_num.max(0) as usize,
+        )
+        .into_iter()
+        .filter(|(_, scores)| !scores.is_empty())
+        .map(|(track, scores)| ReportRanking {
+            track: Some(track),
+            entries: scores
+                .into_iter()
+                .map(|score| ReportEntry {
+                    source: PollReportSource {
+                        team: score.title,
+                        channel: poll.channel.clone(),
+                        total_votes: score.voters.to_string(),
+                        score: score.score,
+                    },
+                    normalized: score.normalized,
+                    criteria: score.criteria,
+                })
+                .collect(),
+        })
+        .collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::data::{
+        normalize_judges, rank_tracks, score_variants, DialogVariant, JudgeNormalization,
+        ScoringMode, SingleVariantSource, VotesResult,
+    };
+    use crate::fixtures::variant_source;
+    use crate::ui_poll_view::Track;
+
+    fn variant(id: i32, title: &str) -> SingleVariantSource {
+        SingleVariantSource {
+            title: Some(title.to_owned()),
+            ..variant_source(id)
+        }
+    }
+
+    fn criterion(id: i32, max_score: i32) -> DialogVariant {
+        DialogVariant {
+            id,
+            day_id: 1,
+            variant_text: format!("criterion {}", id),
+            max_score,
+            weight: 1,
+            track: Track::Audience.as_str().to_owned(),
+        }
+    }
+
+    fn vote(user_id: i32, poll_variant_id: i32, dialog_variant_id: i32, score: i32) -> VotesResult {
+        VotesResult {
+            id: 0,
+            user_id: Some(user_id),
+            day_id: 1,
+            poll_variant_id,
+            dialog_variant_id,
+            score,
+            ballot: None,
+        }
+    }
+
+    fn anonymous_vote(
+        ballot: i64,
+        poll_variant_id: i32,
+        dialog_variant_id: i32,
+        score: i32,
+    ) -> VotesResult {
+        VotesResult {
+            user_id: None,
+            ballot: Some(ballot),
+            ..vote(0, poll_variant_id, dialog_variant_id, score)
+        }
+    }
+
+    fn fixture() -> (
+        Vec<SingleVariantSource>,
+        Vec<DialogVariant>,
+        Vec<VotesResult>,
+    ) {
+        let variants = vec![variant(1, "Alpha"), variant(2, "Beta"), variant(3, "Gamma")];
+        let criteria = vec![criterion(10, 10), criterion(11, 5)];
+        let votes = vec![
+            vote(100, 1, 10, 8),
+            vote(100, 1, 11, 5),
+            vote(101, 1, 10, 6),
+            vote(101, 1, 11, 1),
+            vote(100, 2, 10, 10),
+            vote(100, 2, 11, 4),
+        ];
+        (variants, criteria, votes)
+    }
+
+    #[test]
+    fn test_sum_and_mean() {
+        let (variants, criteria, votes) = fixture();
+        let sum = score_variants(ScoringMode::Sum, &variants, &criteria, &votes, 0);
+        assert_eq!(sum.len(), 2);
+        assert_eq!((sum[0].title.as_str(), sum[0].score), ("Alpha", 20.0));
+        assert_eq!((sum[1].title.as_str(), sum[1].score), ("Beta", 14.0));
+
+        let mean = score_variants(ScoringMode::Mean, &variants, &criteria, &votes, 0);
+        assert_eq!((mean[0].title.as_str(), mean[0].score), ("Beta", 14.0));
+        assert_eq!((mean[1].title.as_str(), mean[1].score), ("Alpha", 10.0));
+        assert_eq!(mean[1].voters, 2);
+    }
+
+    #[test]
+    fn test_normalized_mean_and_min_votes() {
+        let (variants, criteria, votes) = fixture();
+        let normalized =
+            score_variants(ScoringMode::NormalizedMean, &variants, &criteria, &votes, 0);
+        assert_eq!(normalized[0].title, "Beta");
+        assert!((normalized[0].score - 90.0).abs() < 1e-9);
+        assert!((normalized[1].score - 65.0).abs() < 1e-9);
+
+        let limited = score_variants(ScoringMode::Mean, &variants, &criteria, &votes, 2);
+        assert_eq!(limited.len(), 1);
+        assert_eq!(limited[0].title, "Alpha");
+    }
+
+    #[test]
+    fn test_track_rankings() {
+        let (variants, mut criteria, mut votes) = fixture();
+        criteria[1].track = Track::Jury.as_str().to_owned();
+        votes.push(vote(102, 3, 11, 5));
+        let rankings = rank_tracks(
+            ScoringMode::Sum,
+            JudgeNormalization::Off,
+            &variants,
+            &criteria,
+            &votes,
+            0,
+        );
+        assert_eq!(rankings.len(), 2);
+
+        let (track, jury) = &rankings[0];
+        assert_eq!(*track, Track::Jury);
+        let jury = jury
+            .iter()
+            .map(|score| (score.title.as_str(), score.score))
+            .collect::<Vec<_>>();
+        assert_eq!(jury, vec![("Alpha", 6.0), ("Gamma", 5.0), ("Beta", 4.0)]);
+
+        let (track, audience) = &rankings[1];
+        assert_eq!(*track, Track::Audience);
+        assert_eq!(audience.len(), 2);
+        assert_eq!(
+            (audience[0].title.as_str(), audience[0].score),
+            ("Alpha", 14.0)
+        );
+        assert_eq!(audience[0].criteria.len(), 1);
+    }
+
+    #[test]
+    fn test_weighted_criteria() {
+        let (variants, mut criteria, votes) = fixture();
+        criteria[0].weight = 40;
+        criteria[1].weight = 20;
+        let weighted = score_variants(ScoringMode::NormalizedMean, &variants, &criteria, &votes, 0);
+        assert_eq!(weighted[0].title, "Beta");
+        assert!((weighted[0].score - 280.0 / 3.0).abs() < 1e-9);
+        assert!((weighted[1].score - 200.0 / 3.0).abs() < 1e-9);
+
+        let alpha = &weighted[1].criteria;
+        assert_eq!(alpha.len(), 2);
+        assert_eq!(alpha[0].criterion, "criterion 10");
+        assert!((alpha[0].share - 200.0 / 3.0).abs() < 1e-9);
+        assert_eq!((alpha[0].mean, alpha[0].max_score), (7.0, 10));
+        assert_eq!((alpha[1].mean, alpha[1].max_score), (3.0, 5));
+    }
+
+    #[test]
+    fn test_anonymous_ballots() {
+        let (variants, criteria, _) = fixture();
+        let votes = vec![
+            anonymous_vote(1, 3, 10, 8),
+            anonymous_vote(1, 3, 11, 5),
+            anonymous_vote(2, 3, 10, 6),
+            anonymous_vote(2, 3, 11, 1),
+        ];
+        let mean = score_variants(ScoringMode::Mean, &variants, &criteria, &votes, 0);
+        assert_eq!(mean.len(), 1);
+        assert_eq!((mean[0].title.as_str(), mean[0].voters), ("Gamma", 2));
+        assert_eq!(mean[0].score, 10.0);
+    }
+
+    #[test]
+    fn test_judge_normalization() {
+        let (variants, criteria, _) = fixture();
+        let criteria = &criteria[..1];
+        // Judge 100 is lenient and never rated Alpha, the other judges prefer Alpha
+        let votes = vec![
+            vote(100, 2, 10, 10),
+            vote(100, 3, 10, 9),
+            vote(101, 1, 10, 5),
+            vote(101, 3, 10, 1),
+            vote(102, 1, 10, 4),
+            vote(102, 2, 10, 3),
+        ];
+        let raw = score_variants(ScoringMode::Mean, &variants, criteria, &votes, 0);
+        let titles = raw
+            .iter()
+            .map(|score| score.title.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(titles, vec!["Beta", "Gamma", "Alpha"]);
+        assert!(raw.iter().all(|score| score.normalized.is_none()));
+
+        let mut z_scores = raw.clone();
+        normalize_judges(JudgeNormalization::ZScore, criteria, &votes, &mut z_scores);
+        let z_scores = z_scores
+            .iter()
+            .map(|score| (score.title.as_str(), score.score, score.normalized))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            z_scores,
+            vec![
+                ("Alpha", 4.5, Some(1.0)),
+                ("Beta", 6.5, Some(0.0)),
+                ("Gamma", 5.0, Some(-1.0)),
+            ]
+        );
+
+        let mut ranks = raw.clone();
+        normalize_judges(JudgeNormalization::Rank, criteria, &votes, &mut ranks);
+        let ranks = ranks
+            .iter()
+            .map(|score| (score.title.as_str(), score.normalized))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ranks,
+            vec![
+                ("Alpha", Some(100.0)),
+                ("Beta", Some(50.0)),
+                ("Gamma", Some(0.0)),
+            ]
+        );
+
+        let anonymous = vec![anonymous_vote(1, 3, 10, 8)];
+        let mut scores = score_variants(ScoringMode::Mean, &variants, criteria, &anonymous, 0);
+        normalize_judges(
+            JudgeNormalization::ZScore,
+            criteria,
+            &anonymous,
+            &mut scores,
+        );
+        assert_eq!(scores[0].normalized, None);
+    }
+}
@@ -1,5 +1,8 @@
 use crate::data::{Database, FindUser, WriteUser};
+use crate::error::AppError;
+use crate::locale::workspace_messages;
 use crate::schema::{channel_users, poll, poll_variant, votes_results};
+use crate::ui_poll_view::Voter;
 use actix::Handler;
 use diesel::query_dsl::filter_dsl::FilterDsl;
 use diesel::{
@@ -7,6 +10,7 @@ use diesel::{
     PgConnection, Queryable, RunQueryDsl,
 };
 use slacker::UserInfoResponse;
+use std::convert::TryFrom;
 
 #[derive(Clone, Debug, Queryable, PartialEq, Identifiable)]
 #[table_name = "channel_users"]
@@ -14,6 +18,11 @@ pub struct ChannelUser {
     pub id: i32,
     pub user_slack_id: Option<String>,
     pub user_thumbnail: Option<String>,
+    pub tz: Option<String>,
+    pub tz_offset: i32,
+    pub locale: Option<String>,
+    pub display_name: Option<String>,
+    pub real_name: Option<String>,
 }
 
 impl Default for ChannelUser {
@@ -22,6 +31,11 @@ impl Default for ChannelUser {
             id: -1,
             user_slack_id: None,
             user_thumbnail: None,
+            tz: None,
+            tz_offset: 0,
+            locale: None,
+            display_name: None,
+            real_name: None,
         }
     }
 }
@@ -31,58 +45,83 @@ impl Default for ChannelUser {
 pub struct ChannelUserWrite {
     pub user_slack_id: String,
     pub user_thumbnail: Option<String>,
+    pub tz: Option<String>,
+    pub tz_offset: i32,
+    pub locale: Option<String>,
+    pub display_name: Option<String>,
+    pub real_name: Option<String>,
+}
+
+impl From<&ChannelUser> for Voter {
+    /// Voters are named by the display name of their profile, or by the real name when
+    /// they have not set one.
+    fn from(user: &ChannelUser) -> Self {
+        let slack_id = user.user_slack_id.clone().unwrap_or_default();
+        Voter {
+            name: user
+                .display_name
+                .clone()
+                .or_else(|| user.real_name.clone())
+                .unwrap_or_else(|| slack_id.clone()),
+            slack_id,
+            image: user.user_thumbnail.clone(),
+        }
+    }
 }
 
 impl Handler<FindUser> for Database {
-    type Result = Result<ChannelUser, ()>;
+    type Result = Result<ChannelUser, AppError>;
 
     fn handle(&mut self, msg: FindUser, _: &mut Self::Context) -> Self::Result {
         use crate::schema::channel_users::dsl::*;
-        let connection = self.0.get().unwrap();
+        let connection = self.0.get()?;
         channel_users
             .filter(user_slack_id.eq(msg.0))
             .first::<ChannelUser>(&connection)
-            .map_err(|_| ())
+            .map_err(|e| AppError::or_missing(e, workspace_messages().user_not_found))
     }
 }
 
-impl Into<ChannelUserWrite> for WriteUser {
-    fn into(self) -> ChannelUserWrite {
-        ChannelUserWrite {
-            user_slack_id: self.0,
-            user_thumbnail: if self.1.is_empty() {
-                None
-            } else {
-                Some(self.1)
-            },
-        }
+impl TryFrom<UserInfoResponse> for ChannelUserWrite {
+    type Error = AppError;
+
+    fn try_from(response: UserInfoResponse) -> Result<Self, Self::Error> {
+        let user_info = response
+            .user
+            .ok_or_else(|| AppError::Slack("users.info returned no user".to_owned()))?;
+        let profile = user_info.profile;
+        Ok(ChannelUserWrite {
+            user_slack_id: user_info.id,
+            user_thumbnail: non_empty(profile.image_24),
+            tz: user_info.tz,
+            tz_offset: user_info.tz_offset,
+            locale: user_info.locale,
+            display_name: non_empty(profile.display_name),
+            real_name: non_empty(profile.real_name),
+        })
     }
 }
 
-impl Into<ChannelUser> for UserInfoResponse {
-    fn into(self) -> ChannelUser {
-        let user_info = self.user.unwrap();
-        ChannelUser {
-            id: 0,
-            user_slack_id: Some((&user_info).id.clone()),
-            user_thumbnail: Some(user_info.profile.image_24),
-        }
+fn non_empty(value: String) -> Option<String> {
+    if value.trim().is_empty() {
+        None
+    } else {
+        Some(value)
     }
 }
 
 impl Handler<WriteUser> for Database {
-    type Result = Result<ChannelUser, ()>;
+    type Result = Result<ChannelUser, AppError>;
 
     fn handle(&mut self, msg: WriteUser, _: &mut Self::Context) -> Self::Result {
         use crate::schema::channel_users::dsl::*;
-        let connection = self.0.get().unwrap();
-        let user_image = &msg.1.clone();
+        let connection = self.0.get()?;
         insert_into(channel_users)
-            .values::<ChannelUserWrite>(msg.into())
+            .values(&msg.0)
             .on_conflict(user_slack_id)
             .do_update()
-            .set(user_thumbnail.eq(user_image))
+            .set(&msg.0)
             .get_result::<ChannelUser>(&connection)
-            .map_err(|e| println!("Cannot write user {}", e))
+            .map_err(|e| AppError::Database(format!("Cannot write user {}", e)))
     }
 }
@@ -1,18 +1,28 @@
 use crate::data::ChannelUser;
 use crate::data::{
-    Database, PollViewSource, ReadVotesForCurrentDay, ReadVotesForCurrentUser, SingleVariantSource,
-    WriteVotes,
+    BallotWrite, Database, HasAnonymousBallot, PollViewSource, ReadVotesForCurrentUser,
+    ReadVotesForPoll, SingleVariantSource, WithdrawVotes, WriteBallot,
 };
 use crate::diesel::GroupedBy;
-use crate::schema::{channel_users, poll, poll_variant, votes_results};
+use crate::error::AppError;
+use crate::locale::workspace_messages;
+use crate::schema::{anonymous_voters, channel_users, poll, poll_variant, votes_results};
+use crate::timezone::now_utc;
 use crate::ui_poll_view::SingleVariant;
 use actix::Handler;
+use chrono::{DateTime, Utc};
+use diesel::dsl::sql;
+use diesel::pg::upsert::excluded;
 use diesel::query_dsl::filter_dsl::FilterDsl;
 use diesel::query_dsl::methods::OrderDsl;
+use diesel::result::{DatabaseErrorKind, Error};
+use diesel::sql_types::BigInt;
 use diesel::{
-    insert_into, r2d2, update, BelongingToDsl, ExpressionMethods, Identifiable, Insertable,
-    PgConnection, QueryDsl, Queryable, RunQueryDsl,
+    insert_into, r2d2, update, BelongingToDsl, BoolExpressionMethods, Connection,
+    ExpressionMethods, Identifiable, Insertable, PgConnection, QueryDsl, Queryable, RunQueryDsl,
 };
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
 
 #[derive(Clone, Debug, Queryable, Associations, Identifiable, PartialEq)]
 #[belongs_to(SingleVariantSource, foreign_key = "poll_variant_id")]
@@ -20,96 +30,344 @@ use diesel::{
 #[belongs_to(PollViewSource, foreign_key = "day_id")]
 pub struct VotesResult {
     pub id: i32,
-    pub user_id: i32,
+    /// None in anonymous polls
+    pub user_id: Option<i32>,
     pub day_id: i32,
     pub poll_variant_id: i32,
     pub dialog_variant_id: i32,
     pub score: i32,
+    /// Shared by the scores of one anonymous ballot
+    pub ballot: Option<i64>,
 }
 
 #[derive(Clone, Debug, Insertable, PartialEq)]
 #[table_name = "votes_results"]
 pub struct VotesResultWrite {
-    pub user_id: i32,
+    pub user_id: Option<i32>,
     pub day_id: i32,
     pub poll_variant_id: i32,
     pub dialog_variant_id: i32,
     pub score: i32,
+    pub ballot: Option<i64>,
 }
 
-impl Into<VotesResultWrite> for WriteVotes {
-    fn into(self) -> VotesResultWrite {
-        VotesResultWrite {
-            user_id: self.0,
-            day_id: self.1,
-            poll_variant_id: self.2,
-            dialog_variant_id: self.3,
-            score: self.4,
+#[derive(Clone, Debug, Insertable, PartialEq)]
+#[table_name = "anonymous_voters"]
+pub struct AnonymousVoterWrite {
+    pub poll_id: i32,
+    pub poll_variant_id: i32,
+    pub voter_hash: String,
+}
+
+/// Keyed one-way hash of the user in the poll, hex encoded. Without the key
+/// the hash cannot be matched to a user even by reading the database.
+pub fn voter_hash(key: &str, poll_id: i32, user_slack_id: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_varkey(key.as_bytes()).expect("HMAC accepts any key");
+    mac.input(format!("{}:{}", poll_id, user_slack_id).as_bytes());
+    hex::encode(mac.result().code())
+}
+
+/// Why votes for the variant are rejected at `now`, None while voting is open.
+fn voting_closed_reason(
+    poll: &PollViewSource,
+    variant: &SingleVariantSource,
+    now: DateTime<Utc>,
+) -> Option<&'static str> {
+    if poll.is_closed {
+        Some(workspace_messages().poll_closed)
+    } else if variant.is_voting_ended(now) {
+        Some(workspace_messages().variant_finished)
+    } else {
+        None
+    }
+}
+
+/// Votes can be changed only while the poll is open and the variant window has not ended.
+/// Returns the poll of the variant. Runs in the transaction of the vote change: the poll
+/// and the variant stay locked until it ends, so they cannot be closed in between.
+fn ensure_voting_open(
+    connection: &PgConnection,
+    poll_id: i32,
+    variant_id: i32,
+) -> Result<PollViewSource, AppError> {
+    use crate::schema;
+    let poll = diesel::QueryDsl::find(schema::poll::table, poll_id)
+        .for_share()
+        .first::<PollViewSource>(connection)
+        .map_err(|e| AppError::or_missing(e, workspace_messages().poll_not_found))?;
+    let variant = diesel::QueryDsl::filter(
+        diesel::QueryDsl::find(schema::poll_variant::table, variant_id),
+        schema::poll_variant::day_id.eq(poll_id),
+    )
+    .for_share()
+    .first::<SingleVariantSource>(connection)
+    .map_err(|e| AppError::or_missing(e, workspace_messages().variant_not_found))?;
+    match voting_closed_reason(&poll, &variant, now_utc()) {
+        Some(reason) => Err(AppError::State(reason.to_owned())),
+        None => Ok(poll),
+    }
+}
+
+/// Stores the voter hash and the scores apart, nothing links the scores to the voter.
+/// The ballot number is random, a sequence would follow the order in which people voted.
+fn write_anonymous_ballot(
+    connection: &PgConnection,
+    voter: AnonymousVoterWrite,
+    mut rows: Vec<VotesResultWrite>,
+) -> Result<BallotWrite, Error> {
+    connection.transaction::<_, Error, _>(|| {
+        let inserted = insert_into(anonymous_voters::table)
+            .values(&voter)
+            .on_conflict_do_nothing()
+            .execute(connection)?;
+        if inserted == 0 {
+            return Ok(BallotWrite::AnonymousAlreadyVoted);
         }
+        let ballot = diesel::select(sql::<BigInt>("floor(random() * 9007199254740991)::bigint"))
+            .get_result::<i64>(connection)?;
+        for row in rows.iter_mut() {
+            row.user_id = None;
+            row.ballot = Some(ballot);
+        }
+        insert_into(votes_results::table)
+            .values(&rows)
+            .execute(connection)?;
+        Ok(BallotWrite::Written)
+    })
+}
+
+/// A first ballot is a plain insert, so a concurrent first ballot of the same voter fails
+/// on the unique index and the whole transaction is rolled back. Only an edit replaces scores.
+fn write_named_ballot(
+    connection: &PgConnection,
+    msg: &WriteBallot,
+    rows: &[VotesResultWrite],
+) -> Result<BallotWrite, Error> {
+    use crate::schema::votes_results::dsl::*;
+    connection.transaction::<_, Error, _>(|| {
+        if msg.replace {
+            insert_into(votes_results)
+                .values(rows)
+                .on_conflict((user_id, poll_variant_id, dialog_variant_id))
+                .do_update()
+                .set(score.eq(excluded(score)))
+                .execute(connection)?;
+            return Ok(BallotWrite::Written);
+        }
+        let existing = diesel::QueryDsl::filter(
+            votes_results,
+            user_id
+                .eq(msg.user_id)
+                .and(poll_variant_id.eq(msg.variant_id)),
+        )
+        .count()
+        .get_result::<i64>(connection)?;
+        if existing > 0 {
+            return Ok(BallotWrite::AlreadyVoted);
+        }
+        insert_into(votes_results)
+            .values(rows)
+            .execute(connection)?;
+        Ok(BallotWrite::Written)
+    })
+}
+
+/// Result of a ballot write, a unique violation means another submission of the same
+/// voter was written first.
+fn ballot_outcome(
+    result: Result<BallotWrite, Error>,
+    variant_id: i32,
+) -> Result<BallotWrite, AppError> {
+    match result {
+        Err(Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _)) => {
+            println!("Concurrent ballot for variant {} rejected", variant_id);
+            Ok(BallotWrite::AlreadyVoted)
+        }
+        result => result.map_err(|e| AppError::Database(format!("Cannot write ballot {}", e))),
     }
 }
 
-impl Handler<WriteVotes> for Database {
-    type Result = Result<(), ()>;
+impl Handler<WriteBallot> for Database {
+    type Result = Result<BallotWrite, AppError>;
 
-    fn handle(&mut self, msg: WriteVotes, _: &mut Self::Context) -> Self::Result {
-        use crate::schema::poll_variant::dsl::*;
+    fn handle(&mut self, msg: WriteBallot, _: &mut Self::Context) -> Self::Result {
+        let connection = self.0.get()?;
+        connection.transaction::<_, AppError, _>(|| {
+            let current_poll = ensure_voting_open(&connection, msg.poll_id, msg.variant_id)?;
+            let rows = msg
+                .scores
+                .iter()
+                .map(|(dialog_variant, value)| VotesResultWrite {
+                    user_id: Some(msg.user_id),
+                    day_id: msg.poll_id,
+                    poll_variant_id: msg.variant_id,
+                    dialog_variant_id: *dialog_variant,
+                    score: *value,
+                    ballot: None,
+                })
+                .collect::<Vec<_>>();
+            let result = if current_poll.is_anonymous {
+                let voter = AnonymousVoterWrite {
+                    poll_id: msg.poll_id,
+                    poll_variant_id: msg.variant_id,
+                    voter_hash: msg.voter_hash.clone().ok_or_else(|| {
+                        AppError::State(workspace_messages().anonymous_key_missing.to_owned())
+                    })?,
+                };
+                write_anonymous_ballot(&connection, voter, rows)
+            } else {
+                write_named_ballot(&connection, &msg, &rows)
+            };
+            ballot_outcome(result, msg.variant_id)
+        })
+    }
+}
+
+impl Handler<HasAnonymousBallot> for Database {
+    type Result = Result<bool, AppError>;
+
+    fn handle(&mut self, msg: HasAnonymousBallot, _: &mut Self::Context) -> Self::Result {
+        let connection = self.0.get()?;
+        diesel::QueryDsl::filter(
+            anonymous_voters::table,
+            anonymous_voters::poll_variant_id
+                .eq(msg.0)
+                .and(anonymous_voters::voter_hash.eq(msg.1)),
+        )
+        .count()
+        .get_result::<i64>(&connection)
+        .map(|count| count > 0)
+        .map_err(|e| AppError::Database(format!("Cannot read anonymous voters {}", e)))
+    }
+}
+
+impl Handler<WithdrawVotes> for Database {
+    type Result = Result<usize, AppError>;
+
+    fn handle(&mut self, msg: WithdrawVotes, _: &mut Self::Context) -> Self::Result {
         use crate::schema::votes_results::dsl::*;
-        let connection = self.0.get().unwrap();
-        diesel::insert_into(votes_results)
-            .values::<VotesResultWrite>(msg.into())
+        let connection = self.0.get()?;
+        connection.transaction::<_, AppError, _>(|| {
+            ensure_voting_open(&connection, msg.1, msg.2)?;
+            diesel::delete(diesel::QueryDsl::filter(
+                votes_results,
+                user_id
+                    .eq(msg.0)
+                    .and(day_id.eq(msg.1))
+                    .and(poll_variant_id.eq(msg.2)),
+            ))
             .execute(&connection)
-            .map_err(|_| println!("Cannot write result"))
-            .map(|_| ())
+            .map_err(|e| AppError::Database(format!("Cannot withdraw votes {}", e)))
+        })
     }
 }
 
 impl Handler<ReadVotesForCurrentUser> for Database {
-    type Result = Result<Vec<VotesResult>, ()>;
+    type Result = Result<Vec<VotesResult>, AppError>;
 
     fn handle(&mut self, msg: ReadVotesForCurrentUser, _: &mut Self::Context) -> Self::Result {
         use crate::schema;
         use crate::schema::votes_results::dsl::*;
 
-        let connection = self.0.get().unwrap();
-        let current_day: PollViewSource =
-            diesel::QueryDsl::order(schema::poll::table, schema::poll::id.desc())
-                .first::<PollViewSource>(&connection)
-                .expect("Cannot find last poll");
+        let connection = self.0.get()?;
         let user = diesel::QueryDsl::filter(
             schema::channel_users::dsl::channel_users,
-            schema::channel_users::user_slack_id.eq(msg.0),
+            schema::channel_users::user_slack_id.eq(msg.1),
         )
         .first::<ChannelUser>(&connection)
         .unwrap_or(Default::default());
 
-        diesel::QueryDsl::filter(VotesResult::belonging_to(&current_day), user_id.eq(user.id))
-            .load::<VotesResult>(&connection)
-            .map_err(|e| println!("Cannot read votes from table {}", e))
+        diesel::QueryDsl::filter(
+            diesel::QueryDsl::filter(votes_results, day_id.eq(msg.0)),
+            user_id.eq(user.id),
+        )
+        .load::<VotesResult>(&connection)
+        .map_err(|e| AppError::Database(format!("Cannot read votes from table {}", e)))
     }
 }
 
-impl Handler<ReadVotesForCurrentDay> for Database {
-    type Result = Result<Vec<Vec<VotesResult>>, ()>;
+impl Handler<ReadVotesForPoll> for Database {
+    type Result = Result<Vec<Vec<VotesResult>>, AppError>;
 
-    fn handle(&mut self, _: ReadVotesForCurrentDay, _: &mut Self::Context) -> Self::Result {
+    fn handle(&mut self, msg: ReadVotesForPoll, _: &mut Self::Context) -> Self::Result {
         use crate::schema::poll::dsl::*;
-        use crate::schema::poll_variant::dsl::*;
-        use crate::schema::votes_results::dsl::*;
-        let connection = self.0.get().unwrap();
-        let current_day: PollViewSource = poll
-            .load(&connection)
-            .unwrap()
-            .into_iter()
-            .last()
-            .unwrap();
-        let variants: Vec<SingleVariantSource> = SingleVariantSource::belonging_to(&current_day)
+        let connection = self.0.get()?;
+        let current_poll: PollViewSource =
+            diesel::QueryDsl::find(poll, msg.0)
+                .first(&connection)
+                .map_err(|e| AppError::or_missing(e, workspace_messages().poll_not_found))?;
+        let variants: Vec<SingleVariantSource> = SingleVariantSource::belonging_to(&current_poll)
             .load::<SingleVariantSource>(&connection)
-            .expect("Error while loading variants to poll");
+            .map_err(|e| {
+                AppError::Database(format!("Error while loading variants to poll {}", e))
+            })?;
         let votes: Vec<Vec<VotesResult>> = VotesResult::belonging_to(&variants)
             .load::<VotesResult>(&connection)
-            .expect("Error while loading votes to poll")
+            .map_err(|e| AppError::Database(format!("Error while loading votes to poll {}", e)))?
             .grouped_by(&variants);
         Ok(votes)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::data::votes::{ballot_outcome, voting_closed_reason};
+    use crate::data::{voter_hash, BallotWrite, SingleVariantSource};
+    use crate::fixtures::{poll_source, variant_source, POLL_ID};
+    use crate::locale::workspace_messages;
+    use chrono::{Duration, TimeZone, Utc};
+    use diesel::result::{DatabaseErrorKind, Error};
+
+    #[test]
+    fn test_voter_hash() {
+        let hash = voter_hash("key", 12, "U061F7AUR");
+        assert_eq!(hash.len(), 64);
+        assert_eq!(hash, voter_hash("key", 12, "U061F7AUR"));
+        assert_ne!(hash, voter_hash("other key", 12, "U061F7AUR"));
+        assert_ne!(hash, voter_hash("key", 13, "U061F7AUR"));
+        assert_ne!(hash, voter_hash("key", 12, "U061F7AUS"));
+    }
+
+    #[test]
+    fn test_repeated_first_ballot() {
+        let written = ballot_outcome(Ok(BallotWrite::Written), 1);
+        assert_eq!(written.unwrap(), BallotWrite::Written);
+
+        // The insert of the second of two concurrent first ballots hits the unique index
+        let duplicate = Error::DatabaseError(
+            DatabaseErrorKind::UniqueViolation,
+            Box::new("duplicate key value violates unique constraint".to_owned()),
+        );
+        let repeated = ballot_outcome(Err(duplicate), 1);
+        assert_eq!(repeated.unwrap(), BallotWrite::AlreadyVoted);
+
+        assert!(ballot_outcome(Err(Error::NotFound), 1).is_err());
+    }
+
+    #[test]
+    fn test_late_votes() {
+        let now = Utc.ymd(2026, 10, 18).and_hms(12, 0, 0);
+        let mut poll = poll_source(POLL_ID);
+        let mut variant = SingleVariantSource {
+            start_date: now - Duration::hours(1),
+            end_date: Some(now + Duration::minutes(1)),
+            ..variant_source(2)
+        };
+        let text = workspace_messages();
+        assert_eq!(voting_closed_reason(&poll, &variant, now), None);
+
+        variant.end_date = Some(now);
+        assert_eq!(
+            voting_closed_reason(&poll, &variant, now),
+            Some(text.variant_finished)
+        );
+
+        // A closed poll rejects votes even inside the window of the variant
+        variant.end_date = None;
+        poll.is_closed = true;
+        assert_eq!(
+            voting_closed_reason(&poll, &variant, now),
+            Some(text.poll_closed)
+        );
+    }
+}
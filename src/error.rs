@@ -0,0 +1,157 @@
+use crate::actions_response::BlockAction;
+use crate::locale::workspace_messages;
+use crate::validation::ValidationErrors;
+use actix::MailboxError;
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, ResponseError};
+use diesel::r2d2::PoolError;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fmt;
+use std::num::ParseIntError;
+
+/// Error of the interaction pipeline, from the HTTP handlers down to the database actor.
+#[derive(Debug)]
+pub enum AppError {
+    /// Slack api request failed
+    Slack(String),
+    /// Database query or connection pool failed
+    Database(String),
+    /// Payload sent by Slack cannot be parsed
+    Parse(String),
+    /// Request is not possible in the current state, the text is shown to the user as is
+    State(String),
+    /// Modal inputs are invalid, the modal stays open with the messages next to the inputs
+    Validation(ValidationErrors),
+}
+
+impl AppError {
+    pub fn slack<E: fmt::Display>(error: E) -> Self {
+        AppError::Slack(error.to_string())
+    }
+
+    /// Treats a missing row as a state error with `message` for the user.
+    pub fn or_missing(error: diesel::result::Error, message: &str) -> Self {
+        match error {
+            diesel::result::Error::NotFound => AppError::State(message.to_owned()),
+            error => error.into(),
+        }
+    }
+
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            AppError::Slack(_) => StatusCode::BAD_GATEWAY,
+            AppError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::Parse(_) => StatusCode::BAD_REQUEST,
+            AppError::State(_) => StatusCode::CONFLICT,
+            // Slack reads `response_action` only from successful responses
+            AppError::Validation(_) => StatusCode::OK,
+        }
+    }
+
+    pub fn user_message(&self) -> String {
+        let text = workspace_messages();
+        match self {
+            AppError::Slack(_) => text.slack_failed.to_owned(),
+            AppError::Database(_) => text.database_failed.to_owned(),
+            AppError::Parse(_) => text.parse_failed.to_owned(),
+            AppError::State(message) => message.clone(),
+            AppError::Validation(_) => text.check_form.to_owned(),
+        }
+    }
+
+    pub fn ephemeral_body(&self) -> Value {
+        json!({
+            "response_type": "ephemeral",
+            "text": self.user_message(),
+        })
+    }
+
+    /// Slash commands show the body of a response only with status 200.
+    pub fn ephemeral_response(&self) -> HttpResponse {
+        println!("{}", self);
+        HttpResponse::Ok().json(self.ephemeral_body())
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AppError::Slack(e) => write!(f, "Slack api error: {}", e),
+            AppError::Database(e) => write!(f, "Database error: {}", e),
+            AppError::Parse(e) => write!(f, "Cannot parse request: {}", e),
+            AppError::State(e) => write!(f, "Request rejected: {}", e),
+            AppError::Validation(e) => write!(f, "Invalid input: {:?}", e),
+        }
+    }
+}
+
+impl ResponseError for AppError {
+    fn error_response(&self) -> HttpResponse {
+        println!("{}", self);
+        let body = match self {
+            AppError::Validation(errors) => json!({
+                "response_action": "errors",
+                "errors": errors,
+            }),
+            _ => self.ephemeral_body(),
+        };
+        HttpResponse::build(self.status_code()).json(body)
+    }
+}
+
+impl From<diesel::result::Error> for AppError {
+    fn from(error: diesel::result::Error) -> Self {
+        AppError::Database(error.to_string())
+    }
+}
+
+impl From<PoolError> for AppError {
+    fn from(error: PoolError) -> Self {
+        AppError::Database(format!("connection pool: {}", error))
+    }
+}
+
+impl From<MailboxError> for AppError {
+    fn from(error: MailboxError) -> Self {
+        AppError::Database(format!("database actor: {}", error))
+    }
+}
+
+impl From<serde_json::Error> for AppError {
+    fn from(error: serde_json::Error) -> Self {
+        AppError::Parse(error.to_string())
+    }
+}
+
+impl From<ParseIntError> for AppError {
+    fn from(error: ParseIntError) -> Self {
+        AppError::Parse(error.to_string())
+    }
+}
+
+/// The user who triggered a request, told about failures of the work done in background.
+#[derive(Clone, Debug, Default)]
+pub struct Requester {
+    pub user_id: String,
+    /// Empty for interactions inside modals, then the user gets a direct message
+    pub response_url: String,
+}
+
+impl Requester {
+    pub fn from_command(payload: &HashMap<String, String>) -> Self {
+        Requester {
+            user_id: payload.get("user_id").cloned().unwrap_or_default(),
+            response_url: payload.get("response_url").cloned().unwrap_or_default(),
+        }
+    }
+}
+
+impl From<&BlockAction> for Requester {
+    fn from(block_action: &BlockAction) -> Self {
+        Requester {
+            user_id: block_action.user.id.clone(),
+            response_url: block_action.response_url.clone(),
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use serde::Deserialize;
+
+/// Body of an Events API request.
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EventRequest {
+    /// Sent once when the request url is configured, the challenge is echoed back
+    UrlVerification {
+        challenge: String,
+    },
+    EventCallback {
+        event: Event,
+    },
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    AppHomeOpened {
+        user: String,
+        /// `home` or `messages`
+        #[serde(default)]
+        tab: String,
+    },
+    #[serde(other)]
+    Unknown,
+}
+
+#[cfg(test)]
+mod test {
+    use crate::events::{Event, EventRequest};
+
+    #[test]
+    fn test_event_requests() {
+        let verification = serde_json::from_str::<EventRequest>(
+            r#"{"token":"t","challenge":"3eZbrw1a","type":"url_verification"}"#,
+        )
+        .unwrap();
+        match verification {
+            EventRequest::UrlVerification { challenge } => assert_eq!(challenge, "3eZbrw1a"),
+            request => panic!("Unexpected request {:?}", request),
+        }
+        let home_opened = serde_json::from_str::<EventRequest>(
+            r#"{"type":"event_callback","team_id":"T1","event":{
+                "type":"app_home_opened","user":"U061F7AUR","channel":"D0LAN2Q65",
+                "event_ts":"1515449522000016","tab":"home"}}"#,
+        )
+        .unwrap();
+        match home_opened {
+            EventRequest::EventCallback {
+                event: Event::AppHomeOpened { user, tab },
+            } => assert_eq!((user.as_str(), tab.as_str()), ("U061F7AUR", "home")),
+            request => panic!("Unexpected request {:?}", request),
+        }
+        let other = serde_json::from_str::<EventRequest>(
+            r#"{"type":"event_callback","event":{"type":"member_joined_channel","user":"U1"}}"#,
+        )
+        .unwrap();
+        match other {
+            EventRequest::EventCallback {
+                event: Event::Unknown,
+            } => (),
+            request => panic!("Unexpected request {:?}", request),
+        }
+    }
+}
@@ -0,0 +1,81 @@
+//! Polls shared by the unit tests. The builders fill every field with a neutral value,
+//! a test changes only the fields it checks.
+use crate::data::{
+    PollReportSource, PollViewSource, ReportEntry, ReportRanking, SingleVariantSource,
+};
+use crate::ui_poll_view::{PollView, PollVisibility, SingleVariant, Track, Voter};
+use chrono::{DateTime, TimeZone, Utc};
+
+pub const POLL_ID: i32 = 7;
+pub const CHANNEL: &str = "C024BE7LR";
+
+pub fn start_date() -> DateTime<Utc> {
+    Utc.ymd(2026, 10, 18).and_hms(10, 0, 0)
+}
+
+/// Open public poll whose variants have ids from 1 and are titled "Variant {id}".
+pub fn poll_view(variants: i32) -> PollView {
+    PollView::new(POLL_ID, (1..=variants).map(variant).collect(), CHANNEL)
+}
+
+pub fn variant(id: i32) -> SingleVariant {
+    SingleVariant {
+        id: Some(id),
+        start_date: start_date(),
+        ..SingleVariant::new(&format!("Variant {}", id), "")
+    }
+}
+
+/// Voter "Voter {index}", with an avatar when `image` is set.
+pub fn voter(index: usize, image: bool) -> Voter {
+    Voter {
+        slack_id: format!("U{}", index),
+        name: format!("Voter {}", index),
+        image: if image {
+            Some(format!("https://example.com/{}.png", index))
+        } else {
+            None
+        },
+    }
+}
+
+/// Row of an open public poll.
+pub fn poll_source(id: i32) -> PollViewSource {
+    PollViewSource {
+        id,
+        channel: CHANNEL.to_owned(),
+        is_closed: false,
+        visibility: PollVisibility::Public.as_str().to_owned(),
+        is_anonymous: false,
+    }
+}
+
+/// Row of a variant of `POLL_ID` whose voting has started and has no end.
+pub fn variant_source(id: i32) -> SingleVariantSource {
+    SingleVariantSource {
+        id,
+        day_id: POLL_ID,
+        title: Some(format!("Variant {}", id)),
+        start_date: start_date(),
+        ..Default::default()
+    }
+}
+
+/// Ranking of `entries` teams titled "Team {index}", best first.
+pub fn ranking(track: Option<Track>, entries: usize) -> ReportRanking {
+    ReportRanking {
+        track,
+        entries: (0..entries)
+            .map(|index| ReportEntry {
+                source: PollReportSource {
+                    team: format!("Team {}", index),
+                    channel: CHANNEL.to_owned(),
+                    total_votes: "3".to_owned(),
+                    score: 10.0,
+                },
+                normalized: None,
+                criteria: vec![],
+            })
+            .collect(),
+    }
+}
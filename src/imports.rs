@@ -3,19 +3,28 @@ macro_rules! imports {
     () => {
         use crate::actions_response::InteractResponse;
         use crate::application::SlackApplication;
+        use crate::commands::PollCommand;
+        use crate::config::Config;
         use crate::data::*;
+        use crate::error::{AppError, Requester};
+        use crate::events::{Event, EventRequest};
+        use crate::locale::workspace_messages;
+        use crate::signature::{SlackSignature, MAX_BODY_SIZE};
         use crate::slack_ui::{create_poll_view, update_message_response};
-        use crate::ui_poll_view::{PollView, SingleVariant};
+        use crate::ui_poll_view::{
+            PollView, SingleVariant, EDIT_VOTE_ACTION_PREFIX, VOTE_ACTION_PREFIX,
+            WHO_VOTED_ACTION_PREFIX,
+        };
         use actions_response::ActionResponse;
         use actix::Addr;
         use actix_http::http::Method;
         use actix_web::web::{Data, Form};
         use actix_web::{
-            middleware, web, App, Error, HttpRequest, HttpResponse, HttpServer, Responder,
+            middleware, web, App, Either, Error, HttpRequest, HttpResponse, HttpServer, Responder,
         };
         use dotenv::dotenv;
         use slacker::Future;
-        use slacker::{PostMessageResponse, Slacker};
+        use slacker::{PostMessageResponse, Slacker, View};
         use std::collections::HashMap;
         use std::env;
     };
@@ -0,0 +1,542 @@
+use std::fmt::Display;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Index of the workspace locale in `LOCALES`, set once the configuration is loaded.
+static WORKSPACE_LOCALE: AtomicUsize = AtomicUsize::new(0);
+
+const LOCALES: [Locale; 2] = [Locale::Ru, Locale::En];
+
+/// Language of the texts shown in Slack.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Locale {
+    Ru,
+    En,
+}
+
+impl Locale {
+    /// Locale of the workspace, Russian until the configuration sets another one.
+    pub fn workspace() -> Locale {
+        LOCALES[WORKSPACE_LOCALE.load(Ordering::Relaxed)]
+    }
+
+    pub fn set_workspace(self) {
+        let index = LOCALES.iter().position(|locale| *locale == self);
+        WORKSPACE_LOCALE.store(index.unwrap_or_default(), Ordering::Relaxed);
+    }
+
+    /// Parses locales in the Slack format, e.g. `en-US` or `ru-RU`.
+    pub fn from_slack(locale: &str) -> Option<Locale> {
+        let language = locale.split(|c| c == '-' || c == '_').next()?;
+        match language.to_lowercase().as_str() {
+            "ru" => Some(Locale::Ru),
+            "en" => Some(Locale::En),
+            _ => None,
+        }
+    }
+
+    pub fn messages(self) -> &'static Messages {
+        match self {
+            Locale::Ru => &RU,
+            Locale::En => &EN,
+        }
+    }
+}
+
+/// Texts of the workspace locale, used for channel messages and errors.
+pub fn workspace_messages() -> &'static Messages {
+    Locale::workspace().messages()
+}
+
+/// Replaces every `{}` of `template` with the next argument.
+pub fn fill(template: &str, args: &[&dyn Display]) -> String {
+    let mut args = args.iter();
+    let mut text = String::with_capacity(template.len());
+    for (index, part) in template.split("{}").enumerate() {
+        if index > 0 {
+            if let Some(arg) = args.next() {
+                text.push_str(&arg.to_string());
+            }
+        }
+        text.push_str(part);
+    }
+    text
+}
+
+/// Catalog of user-facing texts, `{}` marks the arguments substituted with [`fill`].
+pub struct Messages {
+    pub poll: &'static str,
+    pub poll_header: &'static str,
+    pub poll_closed_header: &'static str,
+    pub vote: &'static str,
+    pub who_voted: &'static str,
+    pub more_voters: &'static str,
+    pub votes_count: &'static str,
+    pub votes_hidden: &'static str,
+    pub voters_hidden: &'static str,
+    pub report_hidden: &'static str,
+    pub no_votes: &'static str,
+    pub variant_voting_ended: &'static str,
+    pub voting_until: &'static str,
+
+    pub create_poll: &'static str,
+    pub poll_channel: &'static str,
+    pub choose_channel: &'static str,
+    pub poll_visibility: &'static str,
+    pub visibility_public: &'static str,
+    pub visibility_counts_only: &'static str,
+    pub visibility_hidden_until_close: &'static str,
+    pub ballot_type: &'static str,
+    pub ballot_named: &'static str,
+    pub ballot_anonymous: &'static str,
+    pub anonymous_key_missing: &'static str,
+    pub poll_jury: &'static str,
+    pub poll_jury_hint: &'static str,
+    pub choose_jury: &'static str,
+    pub variant_title: &'static str,
+    pub variant_title_hint: &'static str,
+    pub variant_text: &'static str,
+    pub start_date: &'static str,
+    pub start_time: &'static str,
+    pub end_date: &'static str,
+    pub end_time: &'static str,
+    pub add_variant: &'static str,
+    pub add_criteria: &'static str,
+    pub next: &'static str,
+
+    pub criteria: &'static str,
+    pub criterion: &'static str,
+    pub criterion_hint: &'static str,
+    pub max_score: &'static str,
+    pub weight: &'static str,
+    pub weight_hint: &'static str,
+    pub criterion_track: &'static str,
+    pub track_jury: &'static str,
+    pub track_audience: &'static str,
+    pub add_criterion: &'static str,
+    pub accept: &'static str,
+
+    pub confirm: &'static str,
+    pub withdraw_vote: &'static str,
+    pub yes: &'static str,
+    pub no: &'static str,
+    pub already_voted: &'static str,
+    pub anonymous_ballot_final: &'static str,
+    pub sorry: &'static str,
+    pub got_it: &'static str,
+    pub poll_closed: &'static str,
+    pub variant_finished: &'static str,
+    pub variant_finished_at: &'static str,
+    pub variant_not_started: &'static str,
+
+    pub results: &'static str,
+    pub places: [&'static str; 3],
+    pub report_votes: &'static str,
+    pub report_points: &'static str,
+    pub report_normalized: &'static str,
+    pub report_criterion: &'static str,
+    pub empty_report: &'static str,
+    pub report_jury_award: &'static str,
+    pub report_audience_award: &'static str,
+
+    pub poll_not_found: &'static str,
+    pub no_polls: &'static str,
+    pub variant_not_found: &'static str,
+    pub user_not_found: &'static str,
+    pub criterion_without_scores: &'static str,
+    pub select_channel_first: &'static str,
+    pub end_before_start: &'static str,
+    pub skipped_time: &'static str,
+    pub slack_failed: &'static str,
+    pub database_failed: &'static str,
+    pub parse_failed: &'static str,
+    pub check_form: &'static str,
+    pub no_rights: &'static str,
+
+    pub field_empty: &'static str,
+    pub pick_date: &'static str,
+    pub pick_time: &'static str,
+    pub pick_end_date: &'static str,
+    pub score_range: &'static str,
+    pub weight_range: &'static str,
+    pub jury_without_criteria: &'static str,
+    pub jury_criteria_without_jury: &'static str,
+    pub no_criteria_for_track: &'static str,
+
+    pub admin_usage: &'static str,
+    pub admin_everywhere: &'static str,
+    pub admin_in_channel: &'static str,
+    pub admin_granted: &'static str,
+    pub admin_already_granted: &'static str,
+    pub admin_revoked: &'static str,
+    pub admin_not_granted: &'static str,
+    pub admins_title: &'static str,
+    pub admin_owner: &'static str,
+    pub admins_empty: &'static str,
+
+    pub command_help: &'static str,
+    pub unknown_command: &'static str,
+    pub missing_poll_id: &'static str,
+    pub invalid_poll_id: &'static str,
+    pub unexpected_argument: &'static str,
+    pub polls_title: &'static str,
+    pub poll_summary: &'static str,
+    pub poll_state_draft: &'static str,
+    pub poll_state_open: &'static str,
+    pub poll_state_closed: &'static str,
+    pub poll_status: &'static str,
+    pub variant_status: &'static str,
+    pub variant_window: &'static str,
+    pub variant_window_from: &'static str,
+
+    pub home_polls: &'static str,
+    pub home_poll_link: &'static str,
+    pub home_poll_draft: &'static str,
+    pub draft_expired: &'static str,
+    pub home_no_polls: &'static str,
+    pub home_more_polls: &'static str,
+    pub home_post: &'static str,
+    pub home_close: &'static str,
+    pub home_report: &'static str,
+    pub home_ballots: &'static str,
+    pub home_variant: &'static str,
+    pub home_score: &'static str,
+    pub home_no_ballots: &'static str,
+    pub home_more_ballots: &'static str,
+    pub home_upcoming: &'static str,
+    pub home_starts_at: &'static str,
+    pub home_no_upcoming: &'static str,
+    pub home_more_upcoming: &'static str,
+}
+
+pub static RU: Messages = Messages {
+    poll: "Голосование",
+    poll_header: "*Голосование*",
+    poll_closed_header: "*Голосование завершено*",
+    vote: "Голосовать",
+    who_voted: "Кто голосовал",
+    more_voters: "и ещё {}",
+    votes_count: "Голосов: {}",
+    votes_hidden: "Голоса скрыты до закрытия голосования",
+    voters_hidden: "Проголосовавшие будут видны после закрытия голосования",
+    report_hidden: "Результаты этого голосования можно опубликовать только после его закрытия",
+    no_votes: "Голосов нет",
+    variant_voting_ended: "Голосование по варианту завершено",
+    voting_until: "Голосование до {}",
+
+    create_poll: "Создать голосование",
+    poll_channel: "Канал для голосования",
+    choose_channel: "Выберите канал",
+    poll_visibility: "Что видно до закрытия",
+    visibility_public: "Проголосовавшие и число голосов",
+    visibility_counts_only: "Только число голосов",
+    visibility_hidden_until_close: "Ничего",
+    ballot_type: "Бюллетени",
+    ballot_named: "Именные",
+    ballot_anonymous: "Анонимные, голос нельзя связать с участником",
+    anonymous_key_missing: "Для анонимных голосований нужен ANONYMOUS_BALLOT_KEY в настройках",
+    poll_jury: "Жюри",
+    poll_jury_hint: "Жюри оценивает по своим критериям, остальные голосуют как зрители",
+    choose_jury: "Выберите членов жюри",
+    variant_title: "Заголовок #{}",
+    variant_title_hint: "Можно в markdown",
+    variant_text: "Вариант #{}",
+    start_date: "Дата начала голосования #{}",
+    start_time: "Время начала голосования #{}",
+    end_date: "Дата окончания голосования #{}",
+    end_time: "Время окончания голосования #{}",
+    add_variant: "Добавить вариант",
+    add_criteria: "Добавить критерии",
+    next: "Далее",
+
+    criteria: "Варианты оценок",
+    criterion: "Критерий #{}",
+    criterion_hint: "Критерий оценки голоса",
+    max_score: "Максимальная оценка #{}",
+    weight: "Вес #{}",
+    weight_hint: "1 по умолчанию, например 40 для критерия на 40%",
+    criterion_track: "Кто оценивает #{}",
+    track_jury: "Жюри",
+    track_audience: "Зрители",
+    add_criterion: "Добавить критерий",
+    accept: "Принять",
+
+    confirm: "Подтвердить",
+    withdraw_vote: "Отозвать голос",
+    yes: "Да",
+    no: "Нет",
+    already_voted: "Ваш голос уже учтён! Чтобы изменить оценки, нажмите «Голосовать» ещё раз.",
+    anonymous_ballot_final:
+        "Ваш анонимный голос уже учтён. Анонимный голос нельзя изменить или отозвать.",
+    sorry: "Увы",
+    got_it: "Понятно",
+    poll_closed: "Голосование завершено, голоса больше не принимаются.",
+    variant_finished: "Голосование по этому варианту завершилось.",
+    variant_finished_at: "Голосование по этому варианту завершилось {}!",
+    variant_not_started: "Голосование еще не началось! Ожидаем {}!",
+
+    results: "*Результаты голосования*",
+    places: [
+        "🏆*Первое место:*\n",
+        "🌟*Второе место:*\n",
+        "🌟*Третье место:*\n",
+    ],
+    report_votes: "Голосов: *{}*",
+    report_points: "Баллов: *{}*",
+    report_normalized: "С поправкой на судей: *{}*",
+    report_criterion: "{} ({}%): {}/{}",
+    empty_report: "Голосование закрыто, но для отчёта пока нет голосов.",
+    report_jury_award: "*Приз жюри*",
+    report_audience_award: "*Приз зрительских симпатий*",
+
+    poll_not_found: "Голосование не найдено.",
+    no_polls: "Голосований пока нет.",
+    variant_not_found: "Вариант голосования не найден.",
+    user_not_found: "Пользователь не найден.",
+    criterion_without_scores: "У критерия «{}» нет допустимых оценок.",
+    select_channel_first: "Сначала выберите канал для голосования.",
+    end_before_start: "Окончание голосования должно быть позже начала.",
+    skipped_time: "Времени {} нет в вашем часовом поясе из-за перевода часов.",
+    slack_failed: "Slack не ответил, попробуйте ещё раз позже.",
+    database_failed: "Не удалось прочитать или сохранить данные, попробуйте позже.",
+    parse_failed: "Не удалось разобрать запрос от Slack.",
+    check_form: "Проверьте заполнение формы.",
+    no_rights: "У вас нет прав организатора для этого действия.",
+
+    field_empty: "Поле не может быть пустым.",
+    pick_date: "Выберите дату.",
+    pick_time: "Выберите время.",
+    pick_end_date: "Выберите дату окончания или очистите время.",
+    score_range: "Оценка должна быть числом от {} до {}.",
+    weight_range: "Вес должен быть числом от {} до {}.",
+    jury_without_criteria: "Для жюри нужен хотя бы один критерий жюри.",
+    jury_criteria_without_jury: "Критерии жюри добавлены, но жюри не выбрано.",
+    no_criteria_for_track: "В этом голосовании нет критериев для вашей группы.",
+
+    admin_usage: "Использование: `/poll admin grant @user [#channel]`, \
+                  `/poll admin revoke @user [#channel]` или `/poll admin list`.",
+    admin_everywhere: "во всех каналах",
+    admin_in_channel: "в канале {}",
+    admin_granted: "{} теперь организатор {}.",
+    admin_already_granted: "{} уже организатор {}.",
+    admin_revoked: "{} больше не организатор {}.",
+    admin_not_granted: "{} не организатор {}.",
+    admins_title: "*Организаторы*",
+    admin_owner: "{} — владелец из настроек",
+    admins_empty: "Организаторов пока нет.",
+
+    command_help: "*Команды голосования*\n\
+                   `/poll create` — создать голосование\n\
+                   `/poll post <id>` — опубликовать голосование в канале\n\
+                   `/poll close <id>` — завершить голосование и опубликовать результаты\n\
+                   `/poll report <id>` — опубликовать текущие результаты\n\
+                   `/poll status <id>` — показать состояние голосования\n\
+                   `/poll list` — последние голосования с их номерами\n\
+                   `/poll admin grant|revoke @user [#channel]`, `/poll admin list` — организаторы",
+    unknown_command: "Неизвестная команда `{}`. Список команд: `/poll help`.",
+    missing_poll_id: "Укажите номер голосования: `/poll {} <id>`, номера есть в `/poll list`.",
+    invalid_poll_id: "`{}` не номер голосования.",
+    unexpected_argument: "Лишний аргумент `{}`. Список команд: `/poll help`.",
+    polls_title: "*Голосования*",
+    poll_summary: "#{} в {} — {}, вариантов: {}, проголосовали: {}",
+    poll_state_draft: "не опубликовано",
+    poll_state_open: "идёт",
+    poll_state_closed: "завершено",
+    poll_status: "*Голосование #{}* в {} — {}",
+    variant_status: "*{}* — {}, {}",
+    variant_window: "с {} до {}",
+    variant_window_from: "с {}",
+
+    home_polls: "*Открытые голосования*",
+    home_poll_link: "<{}|Голосование #{}> в {}",
+    home_poll_draft: "Голосование #{} в {} — не опубликовано",
+    draft_expired: "Черновик голосования устарел, начните заново с /poll create.",
+    home_no_polls: "Открытых голосований нет.",
+    home_more_polls: "и ещё {} голосований",
+    home_post: "Опубликовать",
+    home_close: "Завершить",
+    home_report: "Результаты",
+    home_ballots: "*Ваши оценки*",
+    home_variant: "*{}*, голосование #{}",
+    home_score: "{}: {}/{}",
+    home_no_ballots: "Вы ещё не голосовали.",
+    home_more_ballots: "и ещё {} оценённых вариантов",
+    home_upcoming: "*Скоро*",
+    home_starts_at: "Голосование начнётся {}",
+    home_no_upcoming: "Все варианты уже открыты для голосования.",
+    home_more_upcoming: "и ещё {} вариантов",
+};
+
+pub static EN: Messages = Messages {
+    poll: "Poll",
+    poll_header: "*Poll*",
+    poll_closed_header: "*Poll is closed*",
+    vote: "Vote",
+    who_voted: "Who voted",
+    more_voters: "+{} more",
+    votes_count: "{} votes",
+    votes_hidden: "Votes are hidden until the poll is closed",
+    voters_hidden: "Voters are shown once the poll is closed",
+    report_hidden: "Results of this poll can be posted only after it is closed",
+    no_votes: "No votes",
+    variant_voting_ended: "Voting for this variant has ended",
+    voting_until: "Voting until {}",
+
+    create_poll: "Create a poll",
+    poll_channel: "Channel for the poll",
+    choose_channel: "Choose a channel",
+    poll_visibility: "Visible before the poll is closed",
+    visibility_public: "Voters and vote counts",
+    visibility_counts_only: "Vote counts only",
+    visibility_hidden_until_close: "Nothing",
+    ballot_type: "Ballots",
+    ballot_named: "Named",
+    ballot_anonymous: "Anonymous, votes cannot be linked to voters",
+    anonymous_key_missing: "Anonymous polls need ANONYMOUS_BALLOT_KEY in the configuration",
+    poll_jury: "Jury",
+    poll_jury_hint: "The jury scores its own criteria, everyone else votes as the audience",
+    choose_jury: "Choose the jury members",
+    variant_title: "Title #{}",
+    variant_title_hint: "Markdown is supported",
+    variant_text: "Variant #{}",
+    start_date: "Voting start date #{}",
+    start_time: "Voting start time #{}",
+    end_date: "Voting end date #{}",
+    end_time: "Voting end time #{}",
+    add_variant: "Add a variant",
+    add_criteria: "Add criteria",
+    next: "Next",
+
+    criteria: "Scoring criteria",
+    criterion: "Criterion #{}",
+    criterion_hint: "What the score is given for",
+    max_score: "Maximum score #{}",
+    weight: "Weight #{}",
+    weight_hint: "1 by default, e.g. 40 for a criterion worth 40%",
+    criterion_track: "Scored by #{}",
+    track_jury: "Jury",
+    track_audience: "Audience",
+    add_criterion: "Add a criterion",
+    accept: "Accept",
+
+    confirm: "Confirm",
+    withdraw_vote: "Withdraw the vote",
+    yes: "Yes",
+    no: "No",
+    already_voted: "Your vote is already counted! To change the scores, press «Vote» again.",
+    anonymous_ballot_final:
+        "Your anonymous vote is already counted. Anonymous votes cannot be changed or withdrawn.",
+    sorry: "Sorry",
+    got_it: "Got it",
+    poll_closed: "The poll is closed, votes are no longer accepted.",
+    variant_finished: "Voting for this variant has ended.",
+    variant_finished_at: "Voting for this variant ended {}!",
+    variant_not_started: "Voting has not started yet! It starts {}!",
+
+    results: "*Poll results*",
+    places: [
+        "🏆*First place:*\n",
+        "🌟*Second place:*\n",
+        "🌟*Third place:*\n",
+    ],
+    report_votes: "*{}* votes",
+    report_points: "*{}* points",
+    report_normalized: "*{}* judge-normalized",
+    report_criterion: "{} ({}%): {}/{}",
+    empty_report: "The poll is closed, but there are no votes for the report yet.",
+    report_jury_award: "*Jury award*",
+    report_audience_award: "*Audience award*",
+
+    poll_not_found: "The poll is not found.",
+    no_polls: "There are no polls yet.",
+    variant_not_found: "The poll variant is not found.",
+    user_not_found: "The user is not found.",
+    criterion_without_scores: "Criterion «{}» has no valid scores.",
+    select_channel_first: "Choose a channel for the poll first.",
+    end_before_start: "Voting must end after it starts.",
+    skipped_time: "Time {} does not exist in your timezone because of a clock change.",
+    slack_failed: "Slack did not respond, please try again later.",
+    database_failed: "Cannot read or save the data, please try again later.",
+    parse_failed: "Cannot parse the request from Slack.",
+    check_form: "Check the form.",
+    no_rights: "You need organizer rights for this action.",
+
+    field_empty: "The field cannot be empty.",
+    pick_date: "Pick a date.",
+    pick_time: "Pick a time.",
+    pick_end_date: "Pick the end date or clear the time.",
+    score_range: "The score must be a number from {} to {}.",
+    weight_range: "The weight must be a number from {} to {}.",
+    jury_without_criteria: "The jury needs at least one jury criterion.",
+    jury_criteria_without_jury: "Jury criteria were added, but no jury was chosen.",
+    no_criteria_for_track: "This poll has no criteria for your group.",
+
+    admin_usage: "Usage: `/poll admin grant @user [#channel]`, \
+                  `/poll admin revoke @user [#channel]` or `/poll admin list`.",
+    admin_everywhere: "in every channel",
+    admin_in_channel: "in {}",
+    admin_granted: "{} is now an organizer {}.",
+    admin_already_granted: "{} is already an organizer {}.",
+    admin_revoked: "{} is no longer an organizer {}.",
+    admin_not_granted: "{} is not an organizer {}.",
+    admins_title: "*Organizers*",
+    admin_owner: "{} — owner from the configuration",
+    admins_empty: "There are no organizers yet.",
+
+    command_help: "*Poll commands*\n\
+                   `/poll create` — create a poll\n\
+                   `/poll post <id>` — post the poll to its channel\n\
+                   `/poll close <id>` — close the poll and post the results\n\
+                   `/poll report <id>` — post the current results\n\
+                   `/poll status <id>` — show the state of the poll\n\
+                   `/poll list` — recent polls with their ids\n\
+                   `/poll admin grant|revoke @user [#channel]`, `/poll admin list` — organizers",
+    unknown_command: "Unknown command `{}`. See `/poll help` for the commands.",
+    missing_poll_id: "Add the poll id: `/poll {} <id>`, see `/poll list` for the ids.",
+    invalid_poll_id: "`{}` is not a poll number.",
+    unexpected_argument: "Unexpected argument `{}`. See `/poll help` for the commands.",
+    polls_title: "*Polls*",
+    poll_summary: "#{} in {} — {}, variants: {}, voters: {}",
+    poll_state_draft: "not posted",
+    poll_state_open: "open",
+    poll_state_closed: "closed",
+    poll_status: "*Poll #{}* in {} — {}",
+    variant_status: "*{}* — {}, {}",
+    variant_window: "from {} to {}",
+    variant_window_from: "from {}",
+
+    home_polls: "*Open polls*",
+    home_poll_link: "<{}|Poll #{}> in {}",
+    home_poll_draft: "Poll #{} in {} — not posted",
+    draft_expired: "The poll draft has expired, start again with /poll create.",
+    home_no_polls: "There are no open polls.",
+    home_more_polls: "+{} more polls",
+    home_post: "Post",
+    home_close: "Close",
+    home_report: "Report",
+    home_ballots: "*Your scores*",
+    home_variant: "*{}*, poll #{}",
+    home_score: "{}: {}/{}",
+    home_no_ballots: "You have not voted yet.",
+    home_more_ballots: "+{} more scored variants",
+    home_upcoming: "*Coming up*",
+    home_starts_at: "Voting starts {}",
+    home_no_upcoming: "Every variant is already open for voting.",
+    home_more_upcoming: "+{} more variants",
+};
+
+#[cfg(test)]
+mod test {
+    use crate::locale::{fill, Locale};
+
+    #[test]
+    fn test_locales_and_templates() {
+        assert_eq!(Locale::from_slack("en-US"), Some(Locale::En));
+        assert_eq!(Locale::from_slack("ru-RU"), Some(Locale::Ru));
+        assert_eq!(Locale::from_slack("de-DE"), None);
+        assert_eq!(fill(Locale::En.messages().votes_count, &[&3]), "3 votes");
+        assert_eq!(
+            fill(Locale::Ru.messages().score_range, &[&1, &100]),
+            "Оценка должна быть числом от 1 до 100."
+        );
+    }
+}
@@ -6,123 +6,214 @@ extern crate diesel;
 use actix_http::error;
 use actix_rt::System;
 use actix_web::FromRequest;
+use serde_json::json;
 use std::str::FromStr;
 
 mod actions_response;
 mod application;
+mod commands;
+mod config;
 mod data;
+mod error;
+mod events;
+#[cfg(test)]
+mod fixtures;
 mod imports;
+mod locale;
 mod poll_state;
+mod scheduler;
 mod schema;
+mod signature;
 mod slack_ui;
+mod timezone;
 mod ui_poll_view;
+mod validation;
 
 imports!();
 
 const VARIANT_ADD: &str = "variant_add";
-const CHANNEL_CHOOSE: &str = "channel_choose";
+pub const CHANNEL_CHOOSE: &str = "channel_choose";
 const DIALOG_SETUP: &str = "dialog_setup";
 const DIALOG_VARIANT_ADD: &str = "dialog_variant_add";
 pub const VIEW_POLL_CREATE_ID: &str = "view_poll_create";
 pub const DIALOG_VARIANT_CREATE_ID: &str = "dialog_variant_create";
+/// Buttons of the App Home tab, the rest of the action id is a `/poll` command
+pub const HOME_COMMAND_PREFIX: &str = "home_command:";
 
+/// Answer to an interaction that waits for the database, e.g. the checks of the poll creation view.
+type DeferredResponse = Box<dyn Future<Item = HttpResponse, Error = AppError>>;
+
+/// Interactions are answered after the payload is parsed, the work itself runs in background.
+/// The poll creation view is answered once its jury is checked against the saved criteria.
 #[post("/dialog")]
 fn dialog_response(
-    request: HttpRequest,
     payload: Form<HashMap<String, String>>,
     application: Data<SlackApplication>,
-) -> impl Future<Item = HttpResponse, Error = Error> {
-    let action: ActionResponse =
-        serde_json::from_str::<ActionResponse>(&payload.get("payload").unwrap()).unwrap();
+) -> Result<Either<HttpResponse, DeferredResponse>, AppError> {
+    let payload = payload
+        .get("payload")
+        .ok_or_else(|| AppError::Parse("Interaction without payload".to_owned()))?;
+    let action: ActionResponse = serde_json::from_str::<ActionResponse>(payload)?;
     println!("{:?}", action);
     match action {
         ActionResponse::BlockActions { block_action } => {
-            let action_id = block_action.actions.first().unwrap().action_id.clone();
+            let requester = Requester::from(&block_action);
+            let action = block_action
+                .actions
+                .first()
+                .ok_or_else(|| AppError::Parse("Block action without actions".to_owned()))?;
+            let action_id = action.action_id.clone();
+            let selected_channel = action.selected_channel.clone();
             match action_id.as_str() {
-                VARIANT_ADD => application.add_variant_to_poll(block_action.view.unwrap()),
+                VARIANT_ADD => {
+                    application.add_variant_to_poll(interaction_view(block_action.view)?, requester)
+                }
                 CHANNEL_CHOOSE => application.process_channel_change(
-                    block_action
-                        .actions
-                        .first()
-                        .unwrap()
-                        .selected_channel
-                        .clone()
-                        .unwrap(),
+                    requester,
+                    interaction_view(block_action.view)?.id.unwrap_or_default(),
+                    selected_channel
+                        .ok_or_else(|| AppError::Parse("Channel is not selected".to_owned()))?,
                 ),
-                DIALOG_SETUP => application.show_dialog_create(block_action.trigger_id),
-                DIALOG_VARIANT_ADD => application.add_variant_to_dialog(block_action.view.unwrap()),
-                _ => application.post_dialog_on_request(block_action),
+                DIALOG_SETUP => application.show_dialog_create(
+                    block_action.trigger_id,
+                    interaction_view(block_action.view)?.id.unwrap_or_default(),
+                    requester,
+                ),
+                DIALOG_VARIANT_ADD => application
+                    .add_variant_to_dialog(interaction_view(block_action.view)?, requester),
+                id if id.starts_with(HOME_COMMAND_PREFIX) => {
+                    let command = PollCommand::from_str(&id[HOME_COMMAND_PREFIX.len()..])?;
+                    run_poll_command(
+                        command,
+                        Some(block_action.trigger_id.clone()),
+                        requester,
+                        &application,
+                    )?;
+                }
+                id if id.starts_with(VOTE_ACTION_PREFIX) => {
+                    application.post_dialog_on_request(block_action)?
+                }
+                id if id.starts_with(WHO_VOTED_ACTION_PREFIX) => {
+                    application.show_voters(block_action)?
+                }
+                _ => (),
             }
         }
         ActionResponse::ViewSubmission { block_action } => {
-            let view = block_action.view.as_ref().unwrap();
-            println!("{:?}", view.callback_id);
-            match view.callback_id.as_ref().unwrap_or(&"".to_owned()).as_str() {
-                DIALOG_VARIANT_CREATE_ID => application.save_dialog_info(block_action),
-                VIEW_POLL_CREATE_ID => application.save_poll_info(block_action),
+            let callback_id = block_action
+                .view
+                .as_ref()
+                .and_then(|view| view.callback_id.clone())
+                .unwrap_or_default();
+            log::debug!("View submission {}", callback_id);
+            match callback_id.as_str() {
+                id if id.starts_with(DIALOG_VARIANT_CREATE_ID) => {
+                    application.save_dialog_info(block_action)?
+                }
+                VIEW_POLL_CREATE_ID => {
+                    return application.save_poll_info(block_action).map(Either::B)
+                }
+                id if id.starts_with(VOTE_ACTION_PREFIX)
+                    || id.starts_with(EDIT_VOTE_ACTION_PREFIX) =>
+                {
+                    application.process_vote_submission(block_action)?
+                }
                 _ => (),
             }
         }
-        ActionResponse::DialogSubmission { block_action } => {
-            application.process_dialog_submission(block_action)
-        }
         _ => (),
     };
-    HttpResponse::Ok().respond_to(&request)
+    Ok(Either::A(HttpResponse::Ok().finish()))
 }
 
-#[post("/post_poll")]
-fn post_poll_response(
-    request: HttpRequest,
-    payload: Form<HashMap<String, String>>,
-    application: Data<SlackApplication>,
-) -> impl Future<Item = HttpResponse, Error = Error> {
-    println!("{:?}", payload);
-    if !application.user_admin.is_empty()
-        && application.user_admin != payload[&"user_id".to_owned()]
-    {
-        return HttpResponse::Forbidden().respond_to(&request);
-    }
-    application.post_last_poll_to_channel(payload[&"trigger_id".to_owned()].clone());
-    HttpResponse::Ok().respond_to(&request)
+fn interaction_view(view: Option<View>) -> Result<View, AppError> {
+    view.ok_or_else(|| AppError::Parse("Interaction without view".to_owned()))
 }
 
-#[post("/create_poll")]
-fn create_poll_response(
+/// Every poll management command goes through `/poll <subcommand>`, see `/poll help`.
+/// Usage errors are answered right away, the work itself runs in background.
+#[post("/poll")]
+fn poll_command_response(
     request: HttpRequest,
     payload: Form<HashMap<String, String>>,
     application: Data<SlackApplication>,
 ) -> impl Future<Item = HttpResponse, Error = Error> {
     println!("{:?}", payload);
-    if !application.user_admin.is_empty()
-        && application.user_admin != payload[&"user_id".to_owned()]
-    {
-        return HttpResponse::Forbidden().respond_to(&request);
+    let text = payload.get("text").cloned().unwrap_or_default();
+    PollCommand::from_str(&text)
+        .map_err(AppError::from)
+        .and_then(|command| {
+            run_poll_command(
+                command,
+                payload.get("trigger_id").cloned(),
+                Requester::from_command(&payload),
+                &application,
+            )
+        })
+        .unwrap_or_else(|error| error.ephemeral_response())
+        .respond_to(&request)
+}
+
+/// Runs a command of `/poll` or of a button on the App Home tab.
+fn run_poll_command(
+    command: PollCommand,
+    trigger_id: Option<String>,
+    requester: Requester,
+    application: &SlackApplication,
+) -> Result<HttpResponse, AppError> {
+    match command {
+        PollCommand::Create => {
+            let trigger_id = trigger_id
+                .ok_or_else(|| AppError::Parse("Command without trigger_id".to_owned()))?;
+            application.process_poll_request(trigger_id, requester)
+        }
+        PollCommand::Post(poll_id) => application.post_poll_to_channel(poll_id, requester),
+        PollCommand::Close(poll_id) => {
+            application.close_poll_and_create_report_request(poll_id, requester)
+        }
+        PollCommand::Report(poll_id) => application.post_poll_report(poll_id, requester),
+        PollCommand::Status(poll_id) => application.show_poll_status(poll_id, requester),
+        PollCommand::List => application.show_poll_list(requester),
+        PollCommand::Admin(command) => application.process_admin_command(command, requester),
+        PollCommand::Help => {
+            return Ok(HttpResponse::Ok().json(json!({
+                "response_type": "ephemeral",
+                "text": workspace_messages().command_help,
+            })))
+        }
     }
-    application.process_poll_request(payload[&"trigger_id".to_owned()].clone());
-    HttpResponse::Ok().respond_to(&request)
+    Ok(HttpResponse::Ok().finish())
 }
 
-#[post("/close_and_post_report")]
-fn close_poll_and_post_report_response(
-    request: HttpRequest,
-    payload: Form<HashMap<String, String>>,
+/// Events API callbacks, Slack verifies the request url with a challenge first.
+#[post("/events")]
+fn events_response(
+    event: web::Json<EventRequest>,
     application: Data<SlackApplication>,
-) -> impl Future<Item = HttpResponse, Error = Error> {
-    println!("{:?}", payload);
-    if !application.user_admin.is_empty()
-        && application.user_admin != payload[&"user_id".to_owned()]
-    {
-        return HttpResponse::Forbidden().respond_to(&request);
+) -> HttpResponse {
+    match event.into_inner() {
+        EventRequest::UrlVerification { challenge } => {
+            return HttpResponse::Ok().json(json!({ "challenge": challenge }))
+        }
+        EventRequest::EventCallback {
+            event: Event::AppHomeOpened { user, tab },
+        } if tab == "home" => application.publish_home(user),
+        _ => (),
     }
-    application.close_poll_and_create_report_request(payload[&"trigger_id".to_owned()].clone());
-    HttpResponse::Ok().respond_to(&request)
+    HttpResponse::Ok().finish()
 }
 
 fn main() -> Result<(), std::io::Error> {
     std::env::set_var("RUST_LOG", "actix_web=debug,actix_server=debug");
     let _ = System::new("Poll_application");
-    let application = Data::new(SlackApplication::new());
+    let config = Config::load().unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        std::process::exit(1)
+    });
+    config.locale.set_workspace();
+    let application = Data::new(SlackApplication::new(&config));
+    application.start_voting_scheduler();
+    let signing_secret = config.slack.signing_secret.clone();
     env_logger::init();
     let app = move || {
         App::new()
@@ -130,23 +221,18 @@ fn main() -> Result<(), std::io::Error> {
             .wrap(middleware::Logger::default())
             .service(
                 web::scope("/api/slack")
+                    .wrap(SlackSignature::new(&signing_secret))
                     .data(web::Form::<HashMap<String, String>>::configure(|cfg| {
-                        cfg.limit(10240000)
+                        cfg.limit(MAX_BODY_SIZE)
                     }))
-                    .service(create_poll_response)
+                    .service(poll_command_response)
                     .service(dialog_response)
-                    .service(post_poll_response)
-                    .service(close_poll_and_post_report_response),
+                    .service(events_response),
             )
     };
 
-    let workers = std::env::var("WORKERS").unwrap_or(String::default());
-    let workers_count = usize::from_str(workers.as_str()).unwrap_or(num_cpus::get());
     HttpServer::new(app)
-        .workers(workers_count)
-        .bind(format!(
-            "127.0.0.1:{}",
-            std::env::var("PORT").unwrap_or("8888".to_owned())
-        ))?
+        .workers(config.server.workers)
+        .bind((config.server.host.as_str(), config.server.port))?
         .run()
 }
@@ -1,9 +1,17 @@
-use crate::ui_poll_view::{DialogViewVariant, SingleVariant};
+use crate::ui_poll_view::{DialogViewVariant, PollVisibility, SingleVariant};
+use serde::{Deserialize, Serialize};
 
-#[derive(Default, Debug)]
+/// Poll under construction, stored per author in `poll_drafts` until the creation modal is submitted.
+#[derive(Default, Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct PollData {
     pub ts: String,
     pub poll_channel: String,
+    pub visibility: PollVisibility,
+    /// Scores are stored without the voters, see `voter_hash`
+    pub is_anonymous: bool,
+    /// Slack ids of the jury, the other voters are the audience
+    pub jury: Vec<String>,
     pub poll_variants: Vec<SingleVariant>,
     pub dialog_variants: Vec<DialogViewVariant>,
 }
@@ -0,0 +1,112 @@
+use crate::data::{Database, DeleteStaleDrafts, FinishVariants, ReadEndedVariants, ReadPoll};
+use crate::error::AppError;
+use crate::locale::workspace_messages;
+use crate::slack_ui::update_message_response;
+use actix::{Actor, Addr, AsyncContext, Context};
+use futures::future::Either;
+use futures::Future;
+use slacker::Slacker;
+use std::collections::HashMap;
+use std::time::Duration;
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Periodically refreshes the posted poll messages of variants whose `end_date` has passed.
+/// A variant is marked finished once its message is refreshed, a failed refresh is retried
+/// on the next tick. Abandoned poll drafts are removed on the same tick.
+pub struct VotingScheduler {
+    pub data: Addr<Database>,
+    pub slacker: Slacker,
+}
+
+impl Actor for VotingScheduler {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.run_interval(CHECK_INTERVAL, |scheduler, _| {
+            scheduler.refresh_ended_variants();
+            scheduler.delete_stale_drafts();
+        });
+    }
+}
+
+/// Finished `(poll id, variant id)` pairs grouped by poll, each poll is refreshed once.
+fn group_by_poll(variants: Vec<(i32, i32)>) -> HashMap<i32, Vec<i32>> {
+    let mut polls = HashMap::<i32, Vec<i32>>::new();
+    for (poll_id, variant_id) in variants {
+        polls.entry(poll_id).or_default().push(variant_id);
+    }
+    polls
+}
+
+impl VotingScheduler {
+    fn delete_stale_drafts(&self) {
+        let task = self
+            .data
+            .send(DeleteStaleDrafts)
+            .map_err(AppError::from)
+            .and_then(|deleted| deleted)
+            .map(|_| ())
+            .map_err(|e| println!("Cannot delete stale poll drafts {}", e));
+        actix::spawn(task);
+    }
+
+    fn refresh_ended_variants(&self) {
+        let database = self.data.clone();
+        let slacker = self.slacker.clone();
+        let task = self
+            .data
+            .send(ReadEndedVariants)
+            .map_err(AppError::from)
+            .and_then(|polls| polls)
+            .map_err(|e| println!("Cannot read ended variants {}", e))
+            .map(move |variants| {
+                for (poll_id, variant_ids) in group_by_poll(variants) {
+                    let slacker = slacker.clone();
+                    let finish = database.clone();
+                    let finished_ids = variant_ids.clone();
+                    let update = database
+                        .send(ReadPoll(poll_id))
+                        .map_err(AppError::from)
+                        .and_then(|poll| poll)
+                        .and_then(move |poll| {
+                            if poll.is_closed {
+                                Either::B(futures::future::ok(()))
+                            } else {
+                                println!("Voting window ended for poll {}", poll_id);
+                                Either::A(update_message_response(
+                                    slacker,
+                                    poll,
+                                    Some(variant_ids),
+                                    workspace_messages(),
+                                ))
+                            }
+                        })
+                        .and_then(move |_| {
+                            finish
+                                .send(FinishVariants(finished_ids))
+                                .map_err(AppError::from)
+                                .and_then(|finished| finished)
+                        })
+                        .map(|_| ())
+                        .map_err(move |e| println!("Cannot refresh poll {} {}", poll_id, e));
+                    actix::spawn(update);
+                }
+            });
+        actix::spawn(task);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::scheduler::group_by_poll;
+
+    #[test]
+    fn test_group_by_poll() {
+        let polls = group_by_poll(vec![(1, 10), (2, 20), (1, 11)]);
+        assert_eq!(polls.len(), 2);
+        assert_eq!(polls[&1], vec![10, 11]);
+        assert_eq!(polls[&2], vec![20]);
+        assert!(group_by_poll(vec![]).is_empty());
+    }
+}
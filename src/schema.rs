@@ -1,8 +1,29 @@
+table! {
+    admins (id) {
+        id -> Int4,
+        user_slack_id -> Text,
+        channel -> Nullable<Text>,
+    }
+}
+
+table! {
+    anonymous_voters (poll_variant_id, voter_hash) {
+        poll_id -> Int4,
+        poll_variant_id -> Int4,
+        voter_hash -> Text,
+    }
+}
+
 table! {
     channel_users (id) {
         id -> Int4,
         user_slack_id -> Nullable<Text>,
         user_thumbnail -> Nullable<Text>,
+        tz -> Nullable<Text>,
+        tz_offset -> Int4,
+        locale -> Nullable<Text>,
+        display_name -> Nullable<Text>,
+        real_name -> Nullable<Text>,
     }
 }
 
@@ -12,6 +33,8 @@ table! {
         day_id -> Int4,
         variant_text -> Text,
         max_score -> Int4,
+        weight -> Int4,
+        track -> Text,
     }
 }
 
@@ -20,7 +43,35 @@ table! {
         id -> Int4,
         channel -> Text,
         is_closed -> Bool,
-        time -> Nullable<Text>,
+        visibility -> Text,
+        is_anonymous -> Bool,
+    }
+}
+
+table! {
+    poll_drafts (id) {
+        id -> Int4,
+        user_slack_id -> Text,
+        view_id -> Text,
+        draft -> Text,
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    poll_jury (id) {
+        id -> Int4,
+        poll_id -> Int4,
+        user_slack_id -> Text,
+    }
+}
+
+table! {
+    poll_messages (id) {
+        id -> Int4,
+        poll_id -> Int4,
+        page -> Int4,
+        ts -> Text,
     }
 }
 
@@ -30,26 +81,33 @@ table! {
         day_id -> Int4,
         title -> Nullable<Text>,
         variant -> Nullable<Text>,
-        start_date -> Timestamp,
-        end_date -> Nullable<Timestamp>,
+        start_date -> Timestamptz,
+        end_date -> Nullable<Timestamptz>,
+        is_finished -> Bool,
     }
 }
 
 table! {
     votes_results (id) {
         id -> Int4,
-        user_id -> Int4,
+        user_id -> Nullable<Int4>,
         day_id -> Int4,
         poll_variant_id -> Int4,
         dialog_variant_id -> Int4,
         score -> Int4,
+        ballot -> Nullable<Int8>,
     }
 }
 
 allow_tables_to_appear_in_same_query!(
+    admins,
+    anonymous_voters,
     channel_users,
     dialog_variants,
     poll,
+    poll_drafts,
+    poll_jury,
+    poll_messages,
     poll_variant,
     votes_results,
 );
@@ -0,0 +1,313 @@
+use actix_http::error::PayloadError;
+use actix_http::error::{ErrorPayloadTooLarge, ErrorUnauthorized};
+use actix_http::h1;
+use actix_service::{Service, Transform};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::Error;
+use bytes::BytesMut;
+use chrono::Utc;
+use futures::future::{err, ok, Either, FutureResult};
+use futures::{Future, Poll, Stream};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::str::FromStr;
+
+const SIGNATURE_HEADER: &str = "X-Slack-Signature";
+const TIMESTAMP_HEADER: &str = "X-Slack-Request-Timestamp";
+const SIGNATURE_VERSION: &str = "v0";
+/// Requests older than five minutes are treated as replays.
+pub const MAX_REQUEST_AGE: i64 = 60 * 5;
+/// Slack payloads take a few kilobytes, bigger bodies are rejected before they are buffered.
+pub const MAX_BODY_SIZE: usize = 256 * 1024;
+
+/// Checks `X-Slack-Signature` against the app signing secret
+/// before the request reaches any handler. Requests without a fresh timestamp or a signature
+/// are rejected before their body is read, and the body is read up to `MAX_BODY_SIZE`.
+#[derive(Clone)]
+pub struct SlackSignature {
+    secret: Rc<String>,
+}
+
+impl SlackSignature {
+    pub fn new(secret: &str) -> SlackSignature {
+        SlackSignature {
+            secret: Rc::new(secret.to_owned()),
+        }
+    }
+}
+
+pub struct SlackSignatureMiddleware<S> {
+    service: Rc<RefCell<S>>,
+    secret: Rc<String>,
+}
+
+impl<S, B> Transform<S> for SlackSignature
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = SlackSignatureMiddleware<S>;
+    type Future = FutureResult<Self::Transform, Self::InitError>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(SlackSignatureMiddleware {
+            service: Rc::new(RefCell::new(service)),
+            secret: self.secret.clone(),
+        })
+    }
+}
+
+impl<S, B> Service for SlackSignatureMiddleware<S>
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Box<dyn Future<Item = Self::Response, Error = Self::Error>>;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        self.service.borrow_mut().poll_ready()
+    }
+
+    fn call(&mut self, mut req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let secret = self.secret.clone();
+        let timestamp = header_value(&req, TIMESTAMP_HEADER);
+        let signature = header_value(&req, SIGNATURE_HEADER);
+        if !is_fresh(&timestamp, Utc::now().timestamp()) || !is_signature(&signature) {
+            log::warn!("Rejected request without a fresh slack signature");
+            return Box::new(err::<Self::Response, _>(ErrorUnauthorized(
+                "Invalid slack signature",
+            )));
+        }
+        let content_length = header_value(&req, "Content-Length");
+        if usize::from_str(&content_length).unwrap_or_default() > MAX_BODY_SIZE {
+            return Box::new(err::<Self::Response, _>(ErrorPayloadTooLarge(
+                "Request body is too large",
+            )));
+        }
+        Box::new(
+            req.take_payload()
+                .fold(BytesMut::new(), |mut body, chunk| {
+                    if body.len() + chunk.len() > MAX_BODY_SIZE {
+                        return Err(PayloadError::Overflow);
+                    }
+                    body.extend_from_slice(&chunk);
+                    Ok(body)
+                })
+                .map_err(|error| match error {
+                    PayloadError::Overflow => ErrorPayloadTooLarge("Request body is too large"),
+                    error => Error::from(error),
+                })
+                .and_then(move |body| {
+                    let body = body.freeze();
+                    let is_valid = verify_signature(
+                        &secret,
+                        &timestamp,
+                        &body,
+                        &signature,
+                        Utc::now().timestamp(),
+                    );
+                    if !is_valid {
+                        log::warn!("Rejected request with invalid slack signature");
+                        return Either::B(err(ErrorUnauthorized("Invalid slack signature")));
+                    }
+                    let mut payload = h1::Payload::empty();
+                    payload.unread_data(body);
+                    req.set_payload(payload.into());
+                    Either::A(service.borrow_mut().call(req))
+                }),
+        )
+    }
+}
+
+fn header_value(req: &ServiceRequest, name: &str) -> String {
+    req.headers()
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .to_owned()
+}
+
+/// Whether the request timestamp is within `MAX_REQUEST_AGE` of `now`.
+fn is_fresh(timestamp: &str, now: i64) -> bool {
+    i64::from_str(timestamp)
+        .map(|request_time| (now - request_time).abs() <= MAX_REQUEST_AGE)
+        .unwrap_or(false)
+}
+
+fn is_signature(signature: &str) -> bool {
+    signature.starts_with(&format!("{}=", SIGNATURE_VERSION))
+}
+
+/// Verifies a request the way Slack describes it:
+/// `v0=` + hex(HMAC-SHA256(secret, "v0:{timestamp}:{body}")).
+pub fn verify_signature(
+    secret: &str,
+    timestamp: &str,
+    body: &[u8],
+    signature: &str,
+    now: i64,
+) -> bool {
+    if !is_fresh(timestamp, now) || !is_signature(signature) {
+        return false;
+    }
+    let prefix = format!("{}=", SIGNATURE_VERSION);
+    let expected = match hex::decode(&signature[prefix.len()..]) {
+        Ok(hash) => hash,
+        Err(_) => return false,
+    };
+    signing_mac(secret, timestamp, body)
+        .verify(&expected)
+        .is_ok()
+}
+
+fn signing_mac(secret: &str, timestamp: &str, body: &[u8]) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_varkey(secret.as_bytes()).expect("HMAC accepts any key");
+    mac.input(format!("{}:{}:", SIGNATURE_VERSION, timestamp).as_bytes());
+    mac.input(body);
+    mac
+}
+
+#[cfg(test)]
+pub fn sign(secret: &str, timestamp: &str, body: &[u8]) -> String {
+    format!(
+        "{}={}",
+        SIGNATURE_VERSION,
+        hex::encode(signing_mac(secret, timestamp, body).result().code())
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use crate::signature::{
+        sign, verify_signature, SlackSignature, MAX_BODY_SIZE, MAX_REQUEST_AGE,
+    };
+    use actix_web::http::StatusCode;
+    use actix_web::web::Form;
+    use actix_web::{test, web, App, HttpResponse};
+    use chrono::Utc;
+    use std::collections::HashMap;
+
+    // Example request from the Slack "Verifying requests" guide.
+    const FIXTURE_SECRET: &str = "8f742231b10e8888abcd99yyyzzz85a5";
+    const FIXTURE_TIMESTAMP: &str = "1531420618";
+    const FIXTURE_SIGNATURE: &str =
+        "v0=a2114d57b48eac39b9ad189dd8316235a7b4a8d21a10bd27519666489c69b503";
+    const FIXTURE_BODY: &str = "token=xyzz0WbapA4vBCDEFasx0q6G&team_id=T1DC2JH3J&team_domain=testteamnow&channel_id=G8PSS9T3V&channel_name=foobar&user_id=U2CERLKJA&user_name=roadrunner&command=%2Fwebhook-collect&text=&response_url=https%3A%2F%2Fhooks.slack.com%2Fcommands%2FT1DC2JH3J%2F397700885554%2F96rGlfmibIGlgcZRskXaIFfN&trigger_id=398738663015.47445629121.803a0bc887a14d10d2c447fce8b6703c";
+
+    #[test]
+    fn test_fixture_signature() {
+        let now = 1531420618 + 10;
+        assert!(verify_signature(
+            FIXTURE_SECRET,
+            FIXTURE_TIMESTAMP,
+            FIXTURE_BODY.as_bytes(),
+            FIXTURE_SIGNATURE,
+            now
+        ));
+        assert!(!verify_signature(
+            "wrong_secret",
+            FIXTURE_TIMESTAMP,
+            FIXTURE_BODY.as_bytes(),
+            FIXTURE_SIGNATURE,
+            now
+        ));
+        assert!(!verify_signature(
+            FIXTURE_SECRET,
+            FIXTURE_TIMESTAMP,
+            b"token=forged",
+            FIXTURE_SIGNATURE,
+            now
+        ));
+        assert!(!verify_signature(
+            FIXTURE_SECRET,
+            FIXTURE_TIMESTAMP,
+            FIXTURE_BODY.as_bytes(),
+            FIXTURE_SIGNATURE,
+            now + MAX_REQUEST_AGE
+        ));
+    }
+
+    fn signed_request(timestamp: i64, signature: Option<String>) -> actix_http::Request {
+        signed_body_request(timestamp, signature, FIXTURE_BODY.to_owned())
+    }
+
+    fn signed_body_request(
+        timestamp: i64,
+        signature: Option<String>,
+        body: String,
+    ) -> actix_http::Request {
+        let timestamp = timestamp.to_string();
+        let mut request = test::TestRequest::post()
+            .uri("/api/slack/poll")
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .header("X-Slack-Request-Timestamp", timestamp.as_str());
+        let signature = signature.unwrap_or(sign(FIXTURE_SECRET, &timestamp, body.as_bytes()));
+        request = request.header("X-Slack-Signature", signature.as_str());
+        request.set_payload(body).to_request()
+    }
+
+    fn call(request: actix_http::Request) -> StatusCode {
+        let mut app = test::init_service(
+            App::new().service(
+                web::scope("/api/slack")
+                    .wrap(SlackSignature::new(FIXTURE_SECRET))
+                    .route(
+                        "/poll",
+                        web::post().to(|payload: Form<HashMap<String, String>>| {
+                            assert_eq!(payload["user_id"], "U2CERLKJA");
+                            HttpResponse::Ok()
+                        }),
+                    ),
+            ),
+        );
+        test::call_service(&mut app, request).status()
+    }
+
+    #[test]
+    fn test_endpoint_accepts_signed_payload() {
+        let now = Utc::now().timestamp();
+        assert_eq!(call(signed_request(now, None)), StatusCode::OK);
+    }
+
+    #[test]
+    fn test_endpoint_rejects_forged_payload() {
+        let now = Utc::now().timestamp();
+        assert_eq!(
+            call(signed_request(now, Some(FIXTURE_SIGNATURE.to_owned()))),
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            call(signed_request(now - MAX_REQUEST_AGE - 1, None)),
+            StatusCode::UNAUTHORIZED
+        );
+    }
+
+    #[test]
+    fn test_endpoint_rejects_unsigned_and_oversized_payload() {
+        let unsigned = test::TestRequest::post()
+            .uri("/api/slack/poll")
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .set_payload(FIXTURE_BODY)
+            .to_request();
+        assert_eq!(call(unsigned), StatusCode::UNAUTHORIZED);
+
+        let now = Utc::now().timestamp();
+        let body = format!("{}&text={}", FIXTURE_BODY, "a".repeat(MAX_BODY_SIZE));
+        assert_eq!(
+            call(signed_body_request(now, None, body)),
+            StatusCode::PAYLOAD_TOO_LARGE
+        );
+    }
+}
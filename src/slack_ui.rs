@@ -1,133 +1,594 @@
 use crate::actions_response::BlockAction;
-use crate::data::PollReportSource;
+use crate::data::{
+    DialogVariant, HomeData, PollViewSource, ReportEntry, ReportRanking, SingleVariantSource,
+};
+use crate::error::{AppError, Requester};
+use crate::locale::{fill, Messages};
 use crate::poll_state::PollData;
-use crate::ui_poll_view::PollView;
-use crate::VIEW_POLL_CREATE_ID;
-use chrono::NaiveDateTime;
+use crate::timezone::now_utc;
+use crate::ui_poll_view::{
+    PollView, PollVisibility, SingleVariant, Track, VariantKey, WHO_VOTED_ACTION_PREFIX,
+};
+use crate::validation::{MAX_SCORE, MIN_SCORE};
+use crate::{HOME_COMMAND_PREFIX, VIEW_POLL_CREATE_ID};
+use actix_web::client::Client;
+use chrono::{DateTime, Local, Utc};
 use futures::Future;
-use serde_json::Value;
+use serde_json::{json, Value};
 use slacker::{
     BlockElement, Dialog, DialogElement, DialogOpen, DialogOptionGroup, LayoutBlock,
     MessageVisibility, PostMessage, PostMessageResponse, SlackRequest, Slacker, TextObject,
     UpdateMessage, ViewOpen,
 };
+use std::collections::HashMap;
+
+/// Avatars shown next to a variant, the other voters are counted in a "+N more" element.
+const VISIBLE_VOTERS: usize = 4;
+/// Voter names per section of the who voted modal.
+const VOTERS_PER_SECTION: usize = 50;
+
+/// Slack rejects messages with more than 50 blocks, each variant takes three of them
+/// and the first message also holds the header and a divider.
+pub const VARIANTS_PER_MESSAGE: usize = 15;
 
-//todo change to data base poll time
+/// Updates the posted messages of the poll holding `variant_ids`, every message when `None`.
 pub fn update_message_response(
     slacker: Slacker,
-    ts: String,
     poll_view: PollView,
-) -> Box<dyn Future<Item = (), Error = ()>> {
-    let update = UpdateMessage::new("Poll", &poll_view.channel, &ts)
-        .with_blocks(create_poll_view(poll_view).into());
-    println!("{:?}", update);
-    let request = slacker
-        .post(update)
-        .and_then(|r| {
-            println!("{:?}", r);
-            Ok(())
+    variant_ids: Option<Vec<i32>>,
+    text: &Messages,
+) -> Box<dyn Future<Item = (), Error = AppError>> {
+    let pages = match variant_ids {
+        Some(variant_ids) => variant_pages(&poll_view.variants, &variant_ids),
+        None => (0..poll_view.messages.len()).collect(),
+    };
+    let channel = poll_view.channel.clone();
+    let messages = poll_view.messages.clone();
+    let updates = create_poll_view(poll_view, text)
+        .into_iter()
+        .zip(messages)
+        .enumerate()
+        .filter(|(page, _)| pages.contains(page))
+        .map(|(_, (message, ts))| {
+            let update = UpdateMessage::new(text.poll, &channel, &ts).with_blocks(message.into());
+            println!("{:?}", update);
+            slacker
+                .post(update)
+                .map_err(AppError::slack)
+                .map(|r| println!("{:?}", r))
         })
-        .map_err(|e| println!("{:?}", e));
-    Box::new(request)
+        .collect::<Vec<_>>();
+    Box::new(futures::future::join_all(updates).map(|_| ()))
 }
 
-pub fn create_poll_view(
-    poll_view: PollView,
-) -> impl SlackRequest<PostMessageResponse> + Into<Vec<LayoutBlock>> {
-    let mut poll_request = PostMessage::new("Голосование")
-        .channel_str(&poll_view.channel)
+/// Indexes of the poll messages holding `variant_ids`, see `VARIANTS_PER_MESSAGE`.
+fn variant_pages(variants: &[SingleVariant], variant_ids: &[i32]) -> Vec<usize> {
+    variants
+        .iter()
+        .enumerate()
+        .filter(|(_, variant)| {
+            variant
+                .id
+                .map(|id| variant_ids.contains(&id))
+                .unwrap_or(false)
+        })
+        .map(|(index, _)| index / VARIANTS_PER_MESSAGE)
+        .collect()
+}
+
+/// Messages of the poll, the first one starts with the header, the others continue
+/// the list of variants.
+pub fn create_poll_view(poll_view: PollView, text: &Messages) -> Vec<PostMessage> {
+    let header = if poll_view.is_closed {
+        text.poll_closed_header
+    } else {
+        text.poll_header
+    };
+    let poll_id = poll_view.id.unwrap();
+    let is_closed = poll_view.is_closed;
+    let shows_voters = poll_view.visibility.shows_voters(is_closed);
+    let shows_counts = poll_view.visibility.shows_counts(is_closed);
+    let channel = poll_view.channel;
+    let now = now_utc();
+    let mut messages = vec![PostMessage::new(text.poll)
+        .channel_str(&channel)
         .set_response_type(MessageVisibility::InChannel)
         .add_block(LayoutBlock::new_section(TextObject::new_mrkdwn_text(
-            "*Голосование*",
+            header,
         )))
-        .add_block(LayoutBlock::new_divider());
-    for variant in poll_view.variants {
-        let mut images = Vec::new();
-        variant
-            .images
-            .into_iter()
+        .add_block(LayoutBlock::new_divider())];
+    for (index, variant) in poll_view.variants.into_iter().enumerate() {
+        if index > 0 && index % VARIANTS_PER_MESSAGE == 0 {
+            messages.push(
+                PostMessage::new(text.poll)
+                    .channel_str(&channel)
+                    .set_response_type(MessageVisibility::InChannel),
+            );
+        }
+        let mut poll_request = messages.pop().expect("The first message is always created");
+        let voting_ended = variant.end_date.map(|end| end <= now).unwrap_or(false);
+        let key = VariantKey::new(poll_id, variant.id.unwrap());
+        let visible_voters = if shows_voters {
+            variant.voters.as_slice()
+        } else {
+            &[]
+        };
+        let images = visible_voters
+            .iter()
             .rev()
-            .take(4)
-            .for_each(|url| images.push(BlockElement::new_image(url, "Cannot load".into())));
-        poll_request = poll_request.add_block(LayoutBlock::new_section(
-            TextObject::new_mrkdwn_text(format!("*{}*", &variant.title).as_str()),
+            .filter_map(|voter| {
+                voter
+                    .image
+                    .clone()
+                    .map(|image| BlockElement::new_image(image, voter.name.clone()))
+            })
+            .take(VISIBLE_VOTERS)
+            .collect::<Vec<_>>();
+        let hidden_voters = visible_voters.len() - images.len();
+        let title = LayoutBlock::new_section(TextObject::new_mrkdwn_text(
+            format!("*{}*", &variant.title).as_str(),
         ));
-        poll_request = poll_request.add_block(
-            LayoutBlock::new_section(TextObject::new_mrkdwn_text(&variant.variant)).set_accessory(
-                BlockElement::new_button(
-                    TextObject::new_plain_text("Голосовать"),
-                    variant.id.unwrap().to_string(),
-                ),
-            ),
-        );
+        poll_request = poll_request.add_block(if visible_voters.is_empty() {
+            title
+        } else {
+            title.set_accessory(BlockElement::new_button(
+                TextObject::new_plain_text(text.who_voted),
+                key.to_id_with_prefix(WHO_VOTED_ACTION_PREFIX),
+            ))
+        });
+        let description = LayoutBlock::new_section(TextObject::new_mrkdwn_text(&variant.variant));
+        poll_request = poll_request.add_block(if is_closed || voting_ended {
+            description
+        } else {
+            description.set_accessory(BlockElement::new_button(
+                TextObject::new_plain_text(text.vote),
+                key.to_action_id(),
+            ))
+        });
         let mut context = LayoutBlock::new_context(Vec::<BlockElement>::new());
         if !images.is_empty() {
             context = context.set_elements(images);
         }
-        context = context.add_element(if let Some(count) = variant.votes {
-            BlockElement::new_text_element(format!("{} votes", count).as_str())
-        } else {
-            BlockElement::new_text_element("No votes")
+        if hidden_voters > 0 {
+            context = context.add_element(BlockElement::new_text_element(
+                fill(text.more_voters, &[&hidden_voters]).as_str(),
+            ));
+        }
+        context = context.add_element(match variant.votes {
+            _ if !shows_counts => BlockElement::new_text_element(text.votes_hidden),
+            Some(count) => {
+                BlockElement::new_text_element(fill(text.votes_count, &[&count]).as_str())
+            }
+            None => BlockElement::new_text_element(text.no_votes),
         });
-        poll_request = poll_request.add_block(context)
+        if voting_ended {
+            context =
+                context.add_element(BlockElement::new_text_element(text.variant_voting_ended));
+        } else if let Some(end_date) = variant.end_date {
+            context = context.add_element(BlockElement::new_mrkdwn_text_element(
+                fill(text.voting_until, &[&slack_date(end_date)]).as_str(),
+            ));
+        }
+        messages.push(poll_request.add_block(context));
     }
-    poll_request
+    messages
+}
+
+/// Channel messages are seen by many users, Slack renders the date in the timezone of each viewer.
+pub fn slack_date(date: DateTime<Utc>) -> String {
+    format!(
+        "<!date^{}^{{date_short}} {{time}}|{} UTC>",
+        date.timestamp(),
+        date.format("%Y-%m-%d %H:%M")
+    )
+}
+
+/// Inputs describing one variant of the poll creation view.
+pub const VARIANT_INPUTS: usize = 6;
+
+/// Title, description, start and optional end of variant `number`.
+/// Dates and times are picked in the timezone of the admin.
+pub fn create_variant_inputs(number: usize, text: &Messages) -> Vec<LayoutBlock> {
+    vec![
+        LayoutBlock::new_plain_single_line_text_input(
+            fill(text.variant_title, &[&number]).as_str(),
+            format!("title_text_{}", number),
+            text.variant_title_hint,
+        ),
+        LayoutBlock::new_plain_text_input(
+            fill(text.variant_text, &[&number]).as_str(),
+            format!("variant_text_{}", number),
+        ),
+        create_picker_input(
+            "datepicker",
+            fill(text.start_date, &[&number]).as_str(),
+            format!("start_variant_poll_date_{}", number),
+            false,
+        ),
+        create_picker_input(
+            "timepicker",
+            fill(text.start_time, &[&number]).as_str(),
+            format!("start_variant_poll_time_{}", number),
+            false,
+        ),
+        create_picker_input(
+            "datepicker",
+            fill(text.end_date, &[&number]).as_str(),
+            format!("end_variant_poll_date_{}", number),
+            true,
+        ),
+        create_picker_input(
+            "timepicker",
+            fill(text.end_time, &[&number]).as_str(),
+            format!("end_variant_poll_time_{}", number),
+            true,
+        ),
+    ]
+}
+
+/// Inputs describing one criterion of the criteria view.
+pub const CRITERION_INPUTS: usize = 4;
+
+/// Text, max score, optional weight and voter track of criterion `number`.
+pub fn create_criterion_inputs(number: usize, text: &Messages) -> Vec<LayoutBlock> {
+    let weight_id = format!("dialog_variant_weight_{}", number);
+    vec![
+        LayoutBlock::new_plain_single_line_text_input(
+            fill(text.criterion, &[&number]).as_str(),
+            format!("dialog_variant_text_{}", number),
+            text.criterion_hint,
+        ),
+        LayoutBlock::new_plain_single_line_text_input(
+            fill(text.max_score, &[&number]).as_str(),
+            format!("dialog_variant_max_score_{}", number),
+            "1-100",
+        ),
+        serde_json::from_value(json!({
+            "type": "input",
+            "block_id": weight_id,
+            "optional": true,
+            "label": { "type": "plain_text", "text": fill(text.weight, &[&number]) },
+            "element": {
+                "type": "plain_text_input",
+                "action_id": weight_id,
+                "placeholder": { "type": "plain_text", "text": text.weight_hint },
+            },
+        }))
+        .expect("Weight input block is a valid layout block"),
+        create_select_input(
+            fill(text.criterion_track, &[&number]).as_str(),
+            format!("dialog_variant_track_{}", number),
+            Track::ALL
+                .iter()
+                .map(|track| (track_text(*track, text), track.as_str()))
+                .collect(),
+            Some(Track::default().as_str().to_owned()),
+        ),
+    ]
+}
+
+fn track_text(track: Track, text: &Messages) -> &'static str {
+    match track {
+        Track::Jury => text.track_jury,
+        Track::Audience => text.track_audience,
+    }
+}
+
+/// Input block with a `datepicker` or `timepicker` element, its action id equals the block id.
+fn create_picker_input(
+    element_type: &str,
+    label: &str,
+    block_id: String,
+    optional: bool,
+) -> LayoutBlock {
+    serde_json::from_value(json!({
+        "type": "input",
+        "block_id": block_id,
+        "optional": optional,
+        "label": { "type": "plain_text", "text": label },
+        "element": { "type": element_type, "action_id": block_id },
+    }))
+    .expect("Picker input block is a valid layout block")
+}
+
+/// Input of the withdraw choice in the scoring modal of an existing ballot.
+pub const WITHDRAW_VOTE_INPUT: &str = "withdraw_vote";
+pub const WITHDRAW_VOTE_YES: &str = "yes";
+const WITHDRAW_VOTE_NO: &str = "no";
+const SCORE_INPUT_PREFIX: &str = "score_";
+
+/// Block and action id of the score input of a criterion.
+pub fn score_input_id(criterion_id: i32) -> String {
+    format!("{}{}", SCORE_INPUT_PREFIX, criterion_id)
 }
 
-pub fn create_poll_menu(trigger_id: String) -> impl SlackRequest<PostMessageResponse> {
+/// Scoring modal of a variant with one select per criterion, prefilled with
+/// `previous_scores` when the user edits a ballot.
+pub fn create_vote_view(
+    trigger_id: String,
+    callback_id: &str,
+    variant: &SingleVariantSource,
+    criteria: &[DialogVariant],
+    previous_scores: &HashMap<i32, i32>,
+    text: &Messages,
+) -> ViewOpen {
+    let mut blocks = vec![
+        LayoutBlock::new_section(TextObject::new_mrkdwn_text(
+            format!(
+                "*{}*\n{}",
+                variant.title.clone().unwrap_or_default(),
+                variant.variant.clone().unwrap_or_default()
+            )
+            .as_str(),
+        )),
+        LayoutBlock::new_divider(),
+    ];
+    for criterion in criteria {
+        let scores = (MIN_SCORE..=criterion.max_score.min(MAX_SCORE))
+            .map(|score| score.to_string())
+            .collect::<Vec<_>>();
+        blocks.push(create_select_input(
+            &criterion.variant_text,
+            score_input_id(criterion.id),
+            scores
+                .iter()
+                .map(|score| (score.as_str(), score.as_str()))
+                .collect(),
+            previous_scores
+                .get(&criterion.id)
+                .map(|score| score.to_string()),
+        ));
+    }
+    if !previous_scores.is_empty() {
+        blocks.push(create_select_input(
+            text.withdraw_vote,
+            WITHDRAW_VOTE_INPUT.to_owned(),
+            vec![(text.no, WITHDRAW_VOTE_NO), (text.yes, WITHDRAW_VOTE_YES)],
+            Some(WITHDRAW_VOTE_NO.to_owned()),
+        ));
+    }
+    ViewOpen::new_with_id(trigger_id, callback_id, text.vote, blocks).add_submit(text.confirm)
+}
+
+/// Input block with a `static_select` of `(text, value)` options, its action id equals the block id.
+fn create_select_input(
+    label: &str,
+    block_id: String,
+    options: Vec<(&str, &str)>,
+    initial_value: Option<String>,
+) -> LayoutBlock {
+    let options = options
+        .into_iter()
+        .map(|(text, value)| {
+            json!({
+                "text": { "type": "plain_text", "text": text },
+                "value": value,
+            })
+        })
+        .collect::<Vec<_>>();
+    let mut element = json!({
+        "type": "static_select",
+        "action_id": block_id,
+        "options": options,
+    });
+    if let Some(initial) = initial_value.and_then(|value| {
+        options
+            .iter()
+            .find(|option| option["value"] == value.as_str())
+            .cloned()
+    }) {
+        element["initial_option"] = initial;
+    }
+    serde_json::from_value(json!({
+        "type": "input",
+        "block_id": block_id,
+        "label": { "type": "plain_text", "text": label },
+        "element": element,
+    }))
+    .expect("Select input block is a valid layout block")
+}
+
+/// Modal listing every user who voted for the variant.
+pub fn create_voters_view(
+    trigger_id: String,
+    variant: &SingleVariant,
+    text: &Messages,
+) -> ViewOpen {
+    let mut blocks = vec![LayoutBlock::new_section(TextObject::new_mrkdwn_text(
+        format!("*{}*", variant.title).as_str(),
+    ))];
+    for voters in variant.voters.chunks(VOTERS_PER_SECTION) {
+        let names = voters
+            .iter()
+            .map(|voter| format!("• {}", voter.name))
+            .collect::<Vec<_>>();
+        blocks.push(LayoutBlock::new_section(TextObject::new_mrkdwn_text(
+            names.join("\n").as_str(),
+        )));
+    }
+    if variant.voters.is_empty() {
+        blocks.push(LayoutBlock::new_section(TextObject::new_mrkdwn_text(
+            text.no_votes,
+        )));
+    }
+    ViewOpen::new(trigger_id, text.who_voted, blocks).add_close(text.got_it)
+}
+
+/// Input of the poll visibility in the poll creation view.
+pub const POLL_VISIBILITY_INPUT: &str = "poll_visibility";
+/// Input choosing between named and anonymous ballots in the poll creation view.
+pub const POLL_BALLOT_INPUT: &str = "poll_ballot";
+pub const BALLOT_NAMED: &str = "named";
+pub const BALLOT_ANONYMOUS: &str = "anonymous";
+/// Input of the jury members in the poll creation view.
+pub const POLL_JURY_INPUT: &str = "poll_jury";
+/// Inputs of the poll creation view that are not part of a variant.
+pub const POLL_SETTING_INPUTS: [&str; 3] =
+    [POLL_VISIBILITY_INPUT, POLL_BALLOT_INPUT, POLL_JURY_INPUT];
+
+fn visibility_text(visibility: PollVisibility, text: &Messages) -> &'static str {
+    match visibility {
+        PollVisibility::Public => text.visibility_public,
+        PollVisibility::CountsOnly => text.visibility_counts_only,
+        PollVisibility::HiddenUntilClose => text.visibility_hidden_until_close,
+    }
+}
+
+pub fn create_poll_menu(
+    trigger_id: String,
+    text: &Messages,
+) -> impl SlackRequest<PostMessageResponse> {
     let blocks = vec![
-        LayoutBlock::new_section("Канал для голосования").build(),
+        LayoutBlock::new_section(text.poll_channel).build(),
         LayoutBlock::new_action(vec![BlockElement::new_channel_select(
-            "Выберите канал",
+            text.choose_channel,
             "channel_choose".to_owned(),
         )])
         .build(),
-        LayoutBlock::new_plain_single_line_text_input(
-            "Заголовок #1",
-            "title_text_1".to_owned(),
-            "Можно в markdown",
+        create_select_input(
+            text.poll_visibility,
+            POLL_VISIBILITY_INPUT.to_owned(),
+            PollVisibility::ALL
+                .iter()
+                .map(|visibility| (visibility_text(*visibility, text), visibility.as_str()))
+                .collect(),
+            Some(PollVisibility::default().as_str().to_owned()),
         ),
-        LayoutBlock::new_plain_text_input("Вариант #1", "variant_text_1".to_owned()),
-        LayoutBlock::new_plain_single_line_text_input(
-            "Дата начала голосования #1",
-            "start_variant_poll_date_1".to_owned(),
-            "2015-09-18T23:56:04",
+        create_select_input(
+            text.ballot_type,
+            POLL_BALLOT_INPUT.to_owned(),
+            vec![
+                (text.ballot_named, BALLOT_NAMED),
+                (text.ballot_anonymous, BALLOT_ANONYMOUS),
+            ],
+            Some(BALLOT_NAMED.to_owned()),
         ),
+        serde_json::from_value(json!({
+            "type": "input",
+            "block_id": POLL_JURY_INPUT,
+            "optional": true,
+            "label": { "type": "plain_text", "text": text.poll_jury },
+            "hint": { "type": "plain_text", "text": text.poll_jury_hint },
+            "element": {
+                "type": "multi_users_select",
+                "action_id": POLL_JURY_INPUT,
+                "placeholder": { "type": "plain_text", "text": text.choose_jury },
+            },
+        }))
+        .expect("Jury input block is a valid layout block"),
+    ]
+    .into_iter()
+    .chain(create_variant_inputs(1, text))
+    .chain(vec![
         LayoutBlock::new_action(vec![BlockElement::new_button(
-            "Добавить вариант",
+            text.add_variant,
             "variant_add".to_owned(),
         )])
         .build(),
         LayoutBlock::new_action(vec![BlockElement::new_button(
-            "Добавить критерии",
+            text.add_criteria,
             "dialog_setup".to_owned(),
         )])
         .build(),
-    ];
-    ViewOpen::new_with_id(
-        trigger_id,
-        VIEW_POLL_CREATE_ID,
-        "Создать голосование",
-        blocks,
+    ])
+    .collect();
+    ViewOpen::new_with_id(trigger_id, VIEW_POLL_CREATE_ID, text.create_poll, blocks)
+        .add_submit(text.next)
+}
+
+pub fn show_already_voted_message(
+    client: Slacker,
+    user_id: String,
+    message: &str,
+) -> Box<dyn Future<Item = (), Error = AppError>> {
+    Box::new(
+        client
+            .post(PostMessage::new(message).channel_str(&user_id))
+            .map_err(AppError::slack)
+            .map(|post| println!("Response {:?}", post)),
     )
-    .add_submit("Next")
 }
 
-pub fn show_answered_request_view(
+/// Shows `text` to the requester only: ephemerally through the response url
+/// when Slack provided one, otherwise in a direct message.
+pub fn show_ephemeral_message(
     client: Slacker,
-    block_action: BlockAction,
+    requester: Requester,
+    text: String,
+) -> Box<dyn Future<Item = (), Error = AppError>> {
+    if requester.response_url.is_empty() {
+        Box::new(
+            client
+                .post(PostMessage::new(&text).channel_str(&requester.user_id))
+                .map_err(AppError::slack)
+                .map(|post| println!("Response {:?}", post)),
+        )
+    } else {
+        Box::new(
+            Client::default()
+                .post(&requester.response_url)
+                .send_json(&json!({
+                    "response_type": "ephemeral",
+                    "text": text,
+                }))
+                .map_err(AppError::slack)
+                .map(|response| println!("Response {:?}", response.status())),
+        )
+    }
+}
+
+/// Tells the requester that their request failed.
+pub fn show_error_message(
+    client: Slacker,
+    requester: Requester,
+    error: AppError,
 ) -> Box<dyn Future<Item = (), Error = ()>> {
+    println!("{}", error);
+    Box::new(
+        show_ephemeral_message(client, requester, error.user_message())
+            .map_err(|e| println!("Cannot post error message {}", e)),
+    )
+}
+
+pub fn show_closed_request_view(
+    client: Slacker,
+    block_action: BlockAction,
+    text: &Messages,
+) -> Box<dyn Future<Item = (), Error = AppError>> {
+    Box::new(
+        client
+            .post(
+                ViewOpen::new(
+                    block_action.trigger_id,
+                    text.sorry,
+                    vec![LayoutBlock::new_section(text.poll_closed)],
+                )
+                .add_close(text.got_it),
+            )
+            .map_err(AppError::slack)
+            .map(|post| println!("Response {:?}", post)),
+    )
+}
+
+pub fn show_finished_request_view(
+    client: Slacker,
+    block_action: BlockAction,
+    end_date: String,
+    text: &Messages,
+) -> Box<dyn Future<Item = (), Error = AppError>> {
     Box::new(
         client
             .post(
                 ViewOpen::new(
                     block_action.trigger_id,
-                    "Sorry",
-                    vec![LayoutBlock::new_section("Ваш голос уже учтён! Спасибо!")],
+                    text.sorry,
+                    vec![LayoutBlock::new_section(
+                        fill(text.variant_finished_at, &[&end_date]).as_str(),
+                    )],
                 )
-                .add_submit("Понятно"),
+                .add_close(text.got_it),
             )
-            .map_err(|e| println!("Cannot post message {}", e))
+            .map_err(AppError::slack)
             .map(|post| println!("Response {:?}", post)),
     )
 }
@@ -135,62 +596,506 @@ pub fn show_answered_request_view(
 pub fn show_not_ready_request_view(
     client: Slacker,
     block_action: BlockAction,
-    start_date: NaiveDateTime,
-) -> Box<dyn Future<Item = (), Error = ()>> {
+    start_date: String,
+    text: &Messages,
+) -> Box<dyn Future<Item = (), Error = AppError>> {
     Box::new(
         client
             .post(
                 ViewOpen::new(
                     block_action.trigger_id,
-                    "Sorry",
+                    text.sorry,
                     vec![LayoutBlock::new_section(
-                        format!(
-                            "Голосование еще не началось! Ожидаем {}!",
-                            start_date.format("%Y-%m-%d %H:%M:%S")
-                        )
-                        .as_str(),
+                        fill(text.variant_not_started, &[&start_date]).as_str(),
                     )],
                 )
-                .add_submit("Понятно"),
+                .add_close(text.got_it),
             )
-            .map_err(|e| println!("Cannot post message {}", e))
+            .map_err(AppError::slack)
             .map(|post| println!("Response {:?}", post)),
     )
 }
 
-pub fn create_poll_report_view(
-    poll_view: Vec<PollReportSource>,
-) -> impl SlackRequest<PostMessageResponse> + Into<Vec<LayoutBlock>> {
-    let mut poll_request = PostMessage::new("*Результаты голосования*")
-        .channel_str(&poll_view.first().unwrap().channel)
-        .add_block(LayoutBlock::new_section(TextObject::new_mrkdwn_text(
-            "*Результаты голосования*",
-        )))
-        .add_block(LayoutBlock::new_divider());
+pub fn show_anonymous_voted_request_view(
+    client: Slacker,
+    block_action: BlockAction,
+    text: &Messages,
+) -> Box<dyn Future<Item = (), Error = AppError>> {
+    Box::new(
+        client
+            .post(
+                ViewOpen::new(
+                    block_action.trigger_id,
+                    text.sorry,
+                    vec![LayoutBlock::new_section(text.anonymous_ballot_final)],
+                )
+                .add_close(text.got_it),
+            )
+            .map_err(AppError::slack)
+            .map(|post| println!("Response {:?}", post)),
+    )
+}
 
-    for report in poll_view.into_iter().enumerate() {
+/// Report of the poll with a ranking per voter group, the award of a group goes to its
+/// first place. The points of each variant are followed by the mean score and the weight
+/// of every criterion. Every group starts a new message and long rankings are split into
+/// messages of `VARIANTS_PER_MESSAGE` variants.
+pub fn create_poll_report_view(rankings: Vec<ReportRanking>, text: &Messages) -> Vec<PostMessage> {
+    let channel = rankings
+        .iter()
+        .flat_map(|ranking| ranking.entries.first())
+        .map(|entry| entry.source.channel.clone())
+        .next()
+        .unwrap_or_default();
+    // Polls without a jury keep the single ranking without group headings
+    let shows_tracks = rankings
+        .iter()
+        .any(|ranking| ranking.track == Some(Track::Jury));
+    let mut messages = vec![];
+    for ranking in rankings {
+        let award = match ranking.track {
+            Some(Track::Jury) if shows_tracks => Some(text.report_jury_award),
+            Some(Track::Audience) if shows_tracks => Some(text.report_audience_award),
+            _ => None,
+        };
+        for (page, entries) in ranking.entries.chunks(VARIANTS_PER_MESSAGE).enumerate() {
+            let mut message = PostMessage::new(text.results).channel_str(&channel);
+            if messages.is_empty() {
+                message = message
+                    .add_block(LayoutBlock::new_section(TextObject::new_mrkdwn_text(
+                        text.results,
+                    )))
+                    .add_block(LayoutBlock::new_divider());
+            }
+            if let (0, Some(award)) = (page, award) {
+                message =
+                    message.add_block(LayoutBlock::new_section(TextObject::new_mrkdwn_text(award)));
+            }
+            messages.push(add_report_entries(
+                message,
+                entries,
+                page * VARIANTS_PER_MESSAGE,
+                text,
+            ));
+        }
+    }
+    messages
+}
+
+fn add_report_entries(
+    mut poll_request: PostMessage,
+    entries: &[ReportEntry],
+    first_place: usize,
+    text: &Messages,
+) -> PostMessage {
+    for (place, entry) in entries.iter().enumerate() {
+        let report = &entry.source;
         poll_request =
             poll_request.add_block(LayoutBlock::new_section(TextObject::new_mrkdwn_text(
-                format!("{}{}", convert_to_word(report.0 as i32 + 1), report.1.team).as_str(),
+                format!(
+                    "{}{}",
+                    convert_to_word(first_place + place, text),
+                    report.team
+                )
+                .as_str(),
             )));
-        poll_request = poll_request.add_block(LayoutBlock::new_context(vec![
+        let mut context = LayoutBlock::new_context(vec![
             BlockElement::new_mrkdwn_text_element(
-                format!("*{}* votes", report.1.total_votes).as_str(),
+                fill(text.report_votes, &[&report.total_votes]).as_str(),
             ),
             BlockElement::new_mrkdwn_text_element(
-                format!("*{:.2}* points", report.1.score).as_str(),
+                fill(text.report_points, &[&format!("{:.2}", report.score)]).as_str(),
             ),
-        ]));
+        ]);
+        if let Some(normalized) = entry.normalized {
+            context = context.add_element(BlockElement::new_mrkdwn_text_element(
+                fill(text.report_normalized, &[&format!("{:.2}", normalized)]).as_str(),
+            ));
+        }
+        if !entry.criteria.is_empty() {
+            // A single element keeps the context under the Slack limit of ten elements
+            let breakdown = entry
+                .criteria
+                .iter()
+                .map(|criterion| {
+                    fill(
+                        text.report_criterion,
+                        &[
+                            &criterion.criterion,
+                            &format!("{:.0}", criterion.share),
+                            &format!("{:.1}", criterion.mean),
+                            &criterion.max_score,
+                        ],
+                    )
+                })
+                .collect::<Vec<_>>();
+            context = context.add_element(BlockElement::new_mrkdwn_text_element(
+                breakdown.join(" · ").as_str(),
+            ));
+        }
+        poll_request = poll_request.add_block(context);
     }
     poll_request
 }
 
-fn convert_to_word(num: i32) -> String {
-    match num {
-        1 => "🏆*Первое место:*\n",
-        2 => "🌟*Второе место:*\n",
-        3 => "🌟*Третье место:*\n",
-        _ => "",
+/// Slack shows at most this many blocks in a Home view.
+const HOME_BLOCK_LIMIT: usize = 100;
+const VIEWS_PUBLISH_URL: &str = "https://slack.com/api/views.publish";
+
+/// App Home tab of a user. Polls the user manages get buttons running the matching
+/// `/poll` command, their action id is the command prefixed with `HOME_COMMAND_PREFIX`.
+/// Sections share `HOME_BLOCK_LIMIT`, the entries left out are counted at the end of a section.
+pub fn create_home_view<M>(
+    home: &HomeData,
+    can_manage: M,
+    work_space: &str,
+    text: &Messages,
+) -> Vec<LayoutBlock>
+where
+    M: Fn(&PollViewSource) -> bool,
+{
+    let mut polls = vec![];
+    for poll in &home.polls {
+        let is_managed = can_manage(poll);
+        let channel = format!("<#{}>", poll.channel);
+        let ts = home.messages.get(&poll.id);
+        let description = match ts {
+            Some(ts) => fill(
+                text.home_poll_link,
+                &[
+                    &message_link(work_space, &poll.channel, ts),
+                    &poll.id,
+                    &channel,
+                ],
+            ),
+            None if is_managed => fill(text.home_poll_draft, &[&poll.id, &channel]),
+            None => continue,
+        };
+        let mut entry = vec![LayoutBlock::new_section(TextObject::new_mrkdwn_text(
+            &description,
+        ))];
+        if is_managed {
+            let mut buttons = vec![];
+            if ts.is_none() {
+                buttons.push(home_button(text.home_post, "post", poll.id));
+            }
+            buttons.push(home_button(text.home_close, "close", poll.id));
+            buttons.push(home_button(text.home_report, "report", poll.id));
+            entry.push(LayoutBlock::new_action(buttons).build());
+        }
+        polls.push(entry);
+    }
+
+    let ballots = home
+        .ballots
+        .iter()
+        .map(|ballot| {
+            let scores = ballot
+                .scores
+                .iter()
+                .map(|(criterion, max_score, score)| {
+                    fill(text.home_score, &[criterion, score, max_score])
+                })
+                .collect::<Vec<_>>();
+            vec![LayoutBlock::new_section(TextObject::new_mrkdwn_text(
+                format!(
+                    "{}\n{}",
+                    fill(
+                        text.home_variant,
+                        &[
+                            &ballot.variant.title.clone().unwrap_or_default(),
+                            &ballot.variant.day_id
+                        ]
+                    ),
+                    scores.join(" · ")
+                )
+                .as_str(),
+            ))]
+        })
+        .collect::<Vec<_>>();
+
+    let upcoming = home
+        .upcoming
+        .iter()
+        .map(|variant| {
+            vec![LayoutBlock::new_section(TextObject::new_mrkdwn_text(
+                format!(
+                    "{}\n{}",
+                    fill(
+                        text.home_variant,
+                        &[&variant.title.clone().unwrap_or_default(), &variant.day_id]
+                    ),
+                    fill(text.home_starts_at, &[&slack_date(variant.start_date)])
+                )
+                .as_str(),
+            ))]
+        })
+        .collect::<Vec<_>>();
+
+    let sections = vec![
+        (
+            text.home_polls,
+            polls,
+            text.home_no_polls,
+            text.home_more_polls,
+        ),
+        (
+            text.home_ballots,
+            ballots,
+            text.home_no_ballots,
+            text.home_more_ballots,
+        ),
+        (
+            text.home_upcoming,
+            upcoming,
+            text.home_no_upcoming,
+            text.home_more_upcoming,
+        ),
+    ];
+    // One divider between two sections
+    let dividers = sections.len() - 1;
+    let needs = sections
+        .iter()
+        .map(|(_, entries, _, _)| section_size(entries))
+        .collect::<Vec<_>>();
+    let budgets = share_budget(&needs, HOME_BLOCK_LIMIT - dividers);
+    let mut blocks = vec![];
+    for ((heading, entries, empty, more), budget) in sections.into_iter().zip(budgets) {
+        if !blocks.is_empty() {
+            blocks.push(LayoutBlock::new_divider());
+        }
+        blocks.push(LayoutBlock::new_section(TextObject::new_mrkdwn_text(
+            heading,
+        )));
+        blocks.extend(home_section(entries, budget - 1, empty, more));
+    }
+    blocks
+}
+
+/// Blocks of a Home section with every entry: its heading, the entries or the empty notice.
+fn section_size(entries: &[Vec<LayoutBlock>]) -> usize {
+    1 + entries.iter().map(Vec::len).sum::<usize>().max(1)
+}
+
+/// Splits `total` blocks between sections needing `needs` blocks. Sections smaller than
+/// an equal share get what they need, the rest is shared equally by the bigger ones.
+fn share_budget(needs: &[usize], total: usize) -> Vec<usize> {
+    let mut order = (0..needs.len()).collect::<Vec<_>>();
+    order.sort_by_key(|&index| needs[index]);
+    let mut budgets = vec![0; needs.len()];
+    let mut left = total;
+    for (shared, index) in order.into_iter().enumerate() {
+        budgets[index] = needs[index].min(left / (needs.len() - shared));
+        left -= budgets[index];
+    }
+    budgets
+}
+
+/// Entries of a Home section within `budget` blocks. An entry is never split, when some
+/// entries do not fit the last block tells how many were left out.
+fn home_section(
+    entries: Vec<Vec<LayoutBlock>>,
+    budget: usize,
+    empty: &str,
+    more: &str,
+) -> Vec<LayoutBlock> {
+    if entries.is_empty() {
+        return vec![LayoutBlock::new_context(vec![
+            BlockElement::new_text_element(empty),
+        ])];
+    }
+    let fits = entries.iter().map(Vec::len).sum::<usize>() <= budget;
+    let total = entries.len();
+    let mut blocks = vec![];
+    let mut shown = 0;
+    for entry in entries {
+        // Without room for every entry one block is kept for the count of the others
+        if !fits && blocks.len() + entry.len() >= budget {
+            break;
+        }
+        blocks.extend(entry);
+        shown += 1;
+    }
+    if shown < total {
+        blocks.push(LayoutBlock::new_context(vec![
+            BlockElement::new_text_element(fill(more, &[&(total - shown)]).as_str()),
+        ]));
+    }
+    blocks
+}
+
+fn home_button(label: &str, command: &str, poll_id: i32) -> BlockElement {
+    BlockElement::new_button(
+        label,
+        format!("{}{} {}", HOME_COMMAND_PREFIX, command, poll_id),
+    )
+}
+
+/// Link to a posted message, `ts` without the dot is the message id.
+fn message_link(work_space: &str, channel: &str, ts: &str) -> String {
+    format!(
+        "https://{}.slack.com/archives/{}/p{}",
+        work_space,
+        channel,
+        ts.replace('.', "")
+    )
+}
+
+/// Publishes the Home view of the user with `views.publish`.
+pub fn publish_home_view(
+    api_key: &str,
+    user_id: String,
+    blocks: Vec<LayoutBlock>,
+) -> Box<dyn Future<Item = (), Error = AppError>> {
+    Box::new(
+        Client::default()
+            .post(VIEWS_PUBLISH_URL)
+            .bearer_auth(api_key)
+            .send_json(&json!({
+                "user_id": user_id,
+                "view": { "type": "home", "blocks": blocks },
+            }))
+            .map_err(AppError::slack)
+            .and_then(|mut response| response.json::<Value>().map_err(AppError::slack))
+            .and_then(|body| {
+                if body["ok"].as_bool().unwrap_or(false) {
+                    Ok(())
+                } else {
+                    Err(AppError::Slack(format!(
+                        "Cannot publish home view {}",
+                        body["error"]
+                    )))
+                }
+            }),
+    )
+}
+
+/// Heading of the first three places of the report, counted from zero.
+fn convert_to_word(place: usize, text: &Messages) -> &'static str {
+    text.places.get(place).cloned().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::data::{HomeBallot, HomeData, ReportRanking};
+    use crate::fixtures::{poll_source, poll_view, ranking, variant_source, voter};
+    use crate::locale::{fill, Locale};
+    use crate::slack_ui::{
+        create_home_view, create_poll_report_view, create_poll_view, share_budget, variant_pages,
+    };
+    use crate::ui_poll_view::{PollView, PollVisibility, Track};
+    use slacker::LayoutBlock;
+    use std::collections::HashMap;
+
+    fn poll_block_counts(variants: i32) -> Vec<usize> {
+        create_poll_view(poll_view(variants), Locale::En.messages())
+            .into_iter()
+            .map(|message| Into::<Vec<LayoutBlock>>::into(message).len())
+            .collect()
+    }
+
+    #[test]
+    fn test_poll_pages() {
+        // Header, divider and three blocks per variant, under the limit of 50 blocks
+        assert_eq!(poll_block_counts(15), vec![47]);
+        assert_eq!(poll_block_counts(16), vec![47, 3]);
+        assert_eq!(poll_block_counts(31), vec![47, 45, 3]);
+    }
+
+    #[test]
+    fn test_variant_pages() {
+        let variants = poll_view(31).variants;
+        assert_eq!(variant_pages(&variants, &[1]), vec![0]);
+        assert_eq!(variant_pages(&variants, &[15]), vec![0]);
+        assert_eq!(variant_pages(&variants, &[16]), vec![1]);
+        assert_eq!(variant_pages(&variants, &[31]), vec![2]);
+        assert_eq!(variant_pages(&variants, &[2, 17]), vec![0, 1]);
+        assert!(variant_pages(&variants, &[99]).is_empty());
+    }
+
+    fn block_counts(rankings: Vec<ReportRanking>) -> Vec<usize> {
+        create_poll_report_view(rankings, Locale::En.messages())
+            .into_iter()
+            .map(|message| Into::<Vec<LayoutBlock>>::into(message).len())
+            .collect()
+    }
+
+    #[test]
+    fn test_report_pages() {
+        // Header, divider and two blocks per variant
+        assert_eq!(
+            block_counts(vec![ranking(Some(Track::Audience), 3)]),
+            vec![8]
+        );
+        assert_eq!(
+            block_counts(vec![ranking(Some(Track::Audience), 31)]),
+            vec![32, 30, 2]
+        );
+        // Each group starts a message with its award
+        assert_eq!(
+            block_counts(vec![
+                ranking(Some(Track::Jury), 16),
+                ranking(Some(Track::Audience), 15),
+            ]),
+            vec![33, 2, 31]
+        );
+    }
+
+    fn poll_json(poll: PollView) -> String {
+        let blocks = create_poll_view(poll, Locale::En.messages())
+            .into_iter()
+            .flat_map(Into::<Vec<LayoutBlock>>::into)
+            .collect::<Vec<_>>();
+        serde_json::to_string(&blocks).unwrap()
+    }
+
+    #[test]
+    fn test_more_voters() {
+        let text = Locale::En.messages();
+        let mut crowded = poll_view(1);
+        // Voters without an avatar are counted with the hidden ones
+        crowded.variants[0].voters = (0..6).map(|index| voter(index, index != 0)).collect();
+        crowded.variants[0].votes = Some(6);
+        let json = poll_json(crowded.clone());
+        assert_eq!(json.matches("example.com").count(), 4);
+        assert!(json.contains(&fill(text.more_voters, &[&2])));
+
+        let mut few = poll_view(1);
+        few.variants[0].voters = (0..3).map(|index| voter(index, true)).collect();
+        let json = poll_json(few);
+        assert_eq!(json.matches("example.com").count(), 3);
+        assert!(!json.contains(" more"));
+
+        crowded.visibility = PollVisibility::HiddenUntilClose;
+        let json = poll_json(crowded);
+        assert_eq!(json.matches("example.com").count(), 0);
+        assert!(!json.contains(&fill(text.more_voters, &[&2])));
+        assert!(json.contains(text.votes_hidden));
+    }
+
+    #[test]
+    fn test_home_budget() {
+        assert_eq!(share_budget(&[10, 20, 30], 98), vec![10, 20, 30]);
+        assert_eq!(share_budget(&[2, 121, 61], 98), vec![2, 48, 48]);
+
+        let text = Locale::En.messages();
+        let home = HomeData {
+            polls: (1..=60).map(poll_source).collect(),
+            messages: HashMap::new(),
+            ballots: (1..=30)
+                .map(|id| HomeBallot {
+                    variant: variant_source(id),
+                    scores: vec![],
+                })
+                .collect(),
+            upcoming: vec![],
+        };
+        // Unposted polls of an admin take a section and a row of buttons each
+        let blocks = create_home_view(&home, |_| true, "workspace", text);
+        assert!(blocks.len() <= 100);
+        let json = serde_json::to_string(&blocks).unwrap();
+        assert_eq!(json.matches("Variant ").count(), 30);
+        assert!(json.contains(&fill(text.home_more_polls, &[&29])));
+        assert!(json.contains(text.home_no_upcoming));
     }
-    .to_owned()
 }
@@ -0,0 +1,104 @@
+use crate::data::ChannelUser;
+use chrono::{DateTime, Duration, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+
+pub fn now_utc() -> DateTime<Utc> {
+    Utc::now()
+}
+
+/// Timezone of a Slack user as reported by `users.info` and cached in `channel_users`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct UserTimeZone {
+    /// IANA name, e.g. `Europe/Moscow`
+    pub tz: Option<String>,
+    /// Seconds east of UTC, used when the name is unknown
+    pub tz_offset: i32,
+}
+
+impl UserTimeZone {
+    fn named(&self) -> Option<Tz> {
+        self.tz.as_ref().and_then(|tz| tz.parse::<Tz>().ok())
+    }
+
+    /// Converts a wall clock time of the user to UTC. A time skipped by a DST switch gives `None`,
+    /// a repeated one resolves to the earlier instant.
+    pub fn to_utc(&self, local: NaiveDateTime) -> Option<DateTime<Utc>> {
+        match self.named() {
+            Some(tz) => tz
+                .from_local_datetime(&local)
+                .earliest()
+                .map(|time| time.with_timezone(&Utc)),
+            None => {
+                Some(Utc.from_utc_datetime(&(local - Duration::seconds(i64::from(self.tz_offset)))))
+            }
+        }
+    }
+
+    pub fn from_utc(&self, utc: DateTime<Utc>) -> NaiveDateTime {
+        match self.named() {
+            Some(tz) => utc.with_timezone(&tz).naive_local(),
+            None => utc.naive_utc() + Duration::seconds(i64::from(self.tz_offset)),
+        }
+    }
+
+    /// Wall clock time of the user followed by the name of the timezone,
+    /// e.g. `2019-10-01 10:00 (Europe/Moscow)` or `2019-10-01 10:00 (UTC+03:00)`.
+    pub fn format(&self, utc: DateTime<Utc>) -> String {
+        let name = match self.named() {
+            Some(tz) => tz.name().to_owned(),
+            None => {
+                let sign = if self.tz_offset < 0 { '-' } else { '+' };
+                let minutes = self.tz_offset.abs() / 60;
+                format!("UTC{}{:02}:{:02}", sign, minutes / 60, minutes % 60)
+            }
+        };
+        format!("{} ({})", self.from_utc(utc).format("%Y-%m-%d %H:%M"), name)
+    }
+}
+
+impl From<&ChannelUser> for UserTimeZone {
+    fn from(user: &ChannelUser) -> Self {
+        UserTimeZone {
+            tz: user.tz.clone(),
+            tz_offset: user.tz_offset,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::timezone::UserTimeZone;
+    use chrono::{NaiveDate, TimeZone, Utc};
+
+    #[test]
+    fn test_named_and_offset_zones() {
+        let new_york = UserTimeZone {
+            tz: Some("America/New_York".to_owned()),
+            tz_offset: 0,
+        };
+        let summer = NaiveDate::from_ymd(2019, 7, 1).and_hms(10, 0, 0);
+        let winter = NaiveDate::from_ymd(2019, 12, 1).and_hms(10, 0, 0);
+        let summer_utc = Utc.from_utc_datetime(&(summer + chrono::Duration::hours(4)));
+        assert_eq!(new_york.to_utc(summer), Some(summer_utc));
+        assert_eq!(
+            new_york.to_utc(winter),
+            Some(Utc.from_utc_datetime(&(winter + chrono::Duration::hours(5))))
+        );
+        assert_eq!(new_york.from_utc(summer_utc), summer);
+        assert_eq!(
+            new_york.format(summer_utc),
+            "2019-07-01 10:00 (America/New_York)"
+        );
+        let skipped = NaiveDate::from_ymd(2019, 3, 10).and_hms(2, 30, 0);
+        assert_eq!(new_york.to_utc(skipped), None);
+
+        let moscow = UserTimeZone {
+            tz: None,
+            tz_offset: 3 * 3600,
+        };
+        let moscow_utc = Utc.from_utc_datetime(&(summer - chrono::Duration::hours(3)));
+        assert_eq!(moscow.to_utc(summer), Some(moscow_utc));
+        assert_eq!(moscow.from_utc(moscow_utc), summer);
+        assert_eq!(moscow.format(moscow_utc), "2019-07-01 10:00 (UTC+03:00)");
+    }
+}
@@ -1,7 +1,14 @@
 use crate::data::DialogVariant;
+use crate::validation::DEFAULT_WEIGHT;
 use actix::Message;
-use chrono::{Date, NaiveDateTime, TimeZone};
+use chrono::{Date, DateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
 use std::ops::{Range, RangeInclusive};
+use std::str::FromStr;
+
+pub const VOTE_ACTION_PREFIX: &str = "vote";
+pub const EDIT_VOTE_ACTION_PREFIX: &str = "edit_vote";
+pub const WHO_VOTED_ACTION_PREFIX: &str = "who_voted";
 
 #[derive(Clone, Debug)]
 pub struct PollView {
@@ -9,21 +16,177 @@ pub struct PollView {
     pub variants: Vec<SingleVariant>,
     pub channel: String,
     pub is_closed: bool,
-    pub time: Option<String>,
+    pub visibility: PollVisibility,
+    pub is_anonymous: bool,
+    /// Ts of the posted messages in page order, empty until the poll is posted
+    pub messages: Vec<String>,
 }
 
 impl Message for PollView {
     type Result = Result<String, String>;
 }
 
-#[derive(Clone, Debug)]
+/// What the channel sees of the votes before the poll is closed, chosen when the poll is created.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PollVisibility {
+    /// Avatars, names and counts of the voters
+    Public,
+    /// Vote counts only
+    CountsOnly,
+    /// Nothing until the poll is closed
+    HiddenUntilClose,
+}
+
+impl Default for PollVisibility {
+    fn default() -> Self {
+        PollVisibility::Public
+    }
+}
+
+impl PollVisibility {
+    pub const ALL: [PollVisibility; 3] = [
+        PollVisibility::Public,
+        PollVisibility::CountsOnly,
+        PollVisibility::HiddenUntilClose,
+    ];
+
+    /// Value stored in `poll.visibility`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PollVisibility::Public => "public",
+            PollVisibility::CountsOnly => "counts_only",
+            PollVisibility::HiddenUntilClose => "hidden_until_close",
+        }
+    }
+
+    /// Voters and the full results, closed polls always show them.
+    pub fn shows_voters(self, is_closed: bool) -> bool {
+        is_closed || self == PollVisibility::Public
+    }
+
+    pub fn shows_counts(self, is_closed: bool) -> bool {
+        is_closed || self != PollVisibility::HiddenUntilClose
+    }
+}
+
+impl FromStr for PollVisibility {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        PollVisibility::ALL
+            .iter()
+            .cloned()
+            .find(|visibility| visibility.as_str() == s)
+            .ok_or(())
+    }
+}
+
+/// Voter group of a poll with its own criteria and ranking.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Track {
+    /// Users chosen when the poll is created
+    Jury,
+    /// Everyone else
+    Audience,
+}
+
+impl Default for Track {
+    fn default() -> Self {
+        Track::Audience
+    }
+}
+
+impl Track {
+    /// Tracks in the order of the report.
+    pub const ALL: [Track; 2] = [Track::Jury, Track::Audience];
+
+    /// Value stored in `dialog_variants.track`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Track::Jury => "jury",
+            Track::Audience => "audience",
+        }
+    }
+}
+
+impl FromStr for Track {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Track::ALL
+            .iter()
+            .cloned()
+            .find(|track| track.as_str() == s)
+            .ok_or(())
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct SingleVariant {
     pub id: Option<i32>,
     pub title: String,
     pub variant: String,
-    pub images: Vec<String>,
+    /// Users who voted for the variant, at most one entry per user
+    pub voters: Vec<Voter>,
     pub votes: Option<i32>,
-    pub start_date: NaiveDateTime,
+    pub start_date: DateTime<Utc>,
+    pub end_date: Option<DateTime<Utc>>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Voter {
+    pub slack_id: String,
+    pub name: String,
+    /// Avatar url, None when the profile has no image
+    pub image: Option<String>,
+}
+
+/// Identifies a single poll variant across all open polls.
+/// Used as button `action_id` and dialog `callback_id`, e.g. `vote:12:40`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VariantKey {
+    pub poll_id: i32,
+    pub variant_id: i32,
+}
+
+impl VariantKey {
+    pub fn new(poll_id: i32, variant_id: i32) -> VariantKey {
+        VariantKey {
+            poll_id,
+            variant_id,
+        }
+    }
+
+    pub fn to_action_id(&self) -> String {
+        self.to_id_with_prefix(VOTE_ACTION_PREFIX)
+    }
+
+    pub fn to_id_with_prefix(&self, prefix: &str) -> String {
+        format!("{}:{}:{}", prefix, self.poll_id, self.variant_id)
+    }
+
+    pub fn from_id_with_prefix(prefix: &str, s: &str) -> Result<VariantKey, ()> {
+        let mut parts = s.split(':');
+        match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some(id_prefix), Some(poll_id), Some(variant_id), None) if id_prefix == prefix => {
+                Ok(VariantKey {
+                    poll_id: i32::from_str(poll_id).map_err(|_| ())?,
+                    variant_id: i32::from_str(variant_id).map_err(|_| ())?,
+                })
+            }
+            _ => Err(()),
+        }
+    }
+}
+
+impl FromStr for VariantKey {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        VariantKey::from_id_with_prefix(VOTE_ACTION_PREFIX, s)
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -39,16 +202,18 @@ pub struct PollResult {
     pub points_count: f32,
 }
 
-#[derive(Clone, Debug)]
-pub struct DialogView {
-    pub title: String,
-    pub variants: Vec<DialogViewVariant>,
-}
-
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct DialogViewVariant {
     pub variant_text: String,
     pub max_score: RangeInclusive<i32>,
+    #[serde(default = "default_weight")]
+    pub weight: i32,
+    #[serde(default)]
+    pub track: Track,
+}
+
+fn default_weight() -> i32 {
+    DEFAULT_WEIGHT
 }
 
 impl Into<DialogViewVariant> for DialogVariant {
@@ -59,40 +224,27 @@ impl Into<DialogViewVariant> for DialogVariant {
         DialogViewVariant {
             variant_text: self.variant_text,
             max_score: 1..=self.max_score,
+            weight: self.weight,
+            track: self.track(),
         }
     }
 }
 
-impl Into<DialogView> for Vec<DialogVariant> {
-    fn into(self) -> DialogView {
-        DialogView {
-            title: String::new(),
-            variants: self.into_iter().map(Into::into).collect(),
-        }
-    }
-}
-
-impl DialogView {
-    pub fn set_title(mut self, title: String) -> Self {
-        self.title = title;
-        self
-    }
-}
-
 impl SingleVariant {
-    pub fn new(title: &str, variant: &str, images: Vec<&str>) -> SingleVariant {
+    pub fn new(title: &str, variant: &str) -> SingleVariant {
         SingleVariant {
             id: None,
             title: title.to_owned(),
             variant: variant.to_owned(),
-            images: images.into_iter().map(|e| e.to_owned()).collect(),
+            voters: vec![],
             votes: None,
-            start_date: NaiveDateTime::from_timestamp(1, 1),
+            start_date: Utc.timestamp(1, 1),
+            end_date: None,
         }
     }
 
-    pub fn add_image(mut self, image: &str) -> SingleVariant {
-        self.images.push(image.to_owned());
+    pub fn add_voter(mut self, voter: Voter) -> SingleVariant {
+        self.voters.push(voter);
         self
     }
 }
@@ -104,7 +256,37 @@ impl PollView {
             variants,
             channel: channel.to_owned(),
             is_closed: false,
-            time: None,
+            visibility: PollVisibility::Public,
+            is_anonymous: false,
+            messages: vec![],
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::ui_poll_view::{PollVisibility, Track};
+    use std::str::FromStr;
+
+    #[test]
+    fn test_poll_visibility() {
+        for visibility in PollVisibility::ALL.iter() {
+            assert_eq!(
+                PollVisibility::from_str(visibility.as_str()),
+                Ok(*visibility)
+            );
         }
+        assert_eq!(PollVisibility::from_str("secret"), Err(()));
+        assert_eq!(Track::from_str("jury"), Ok(Track::Jury));
+        assert_eq!(
+            Track::from_str(Track::default().as_str()),
+            Ok(Track::Audience)
+        );
+        assert!(PollVisibility::Public.shows_voters(false));
+        assert!(!PollVisibility::CountsOnly.shows_voters(false));
+        assert!(PollVisibility::CountsOnly.shows_counts(false));
+        assert!(!PollVisibility::HiddenUntilClose.shows_counts(false));
+        assert!(PollVisibility::HiddenUntilClose.shows_voters(true));
+        assert!(PollVisibility::HiddenUntilClose.shows_counts(true));
     }
 }
@@ -0,0 +1,180 @@
+use crate::locale::{fill, workspace_messages};
+use crate::ui_poll_view::{DialogViewVariant, Track};
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+pub const MIN_SCORE: i32 = 1;
+pub const MAX_SCORE: i32 = 100;
+/// Weight of a criterion left empty in the criteria view.
+pub const DEFAULT_WEIGHT: i32 = 1;
+pub const MAX_WEIGHT: i32 = 100;
+
+/// Messages for the modal inputs keyed by `block_id`, sent back as `response_action: errors`.
+pub type ValidationErrors = HashMap<String, String>;
+
+/// Stores the message of an invalid input under its block id.
+pub fn check<T>(
+    errors: &mut ValidationErrors,
+    block_id: &Option<String>,
+    result: Result<T, String>,
+) -> Option<T> {
+    match result {
+        Ok(value) => Some(value),
+        Err(message) => {
+            errors.insert(block_id.clone().unwrap_or_default(), message);
+            None
+        }
+    }
+}
+
+pub fn parse_text(value: Option<&str>) -> Result<String, String> {
+    match value.map(str::trim) {
+        Some(text) if !text.is_empty() => Ok(text.to_owned()),
+        _ => Err(workspace_messages().field_empty.to_owned()),
+    }
+}
+
+pub fn parse_picked_date(value: Option<&str>) -> Result<NaiveDate, String> {
+    value
+        .and_then(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok())
+        .ok_or_else(|| workspace_messages().pick_date.to_owned())
+}
+
+pub fn parse_picked_time(value: Option<&str>) -> Result<NaiveTime, String> {
+    value
+        .and_then(|time| NaiveTime::parse_from_str(time, "%H:%M").ok())
+        .ok_or_else(|| workspace_messages().pick_time.to_owned())
+}
+
+/// The end of a voting window is optional, a date without a time means the end of that day.
+pub fn parse_window_end(
+    date: Option<&str>,
+    time: Option<&str>,
+) -> Result<Option<NaiveDateTime>, String> {
+    match (date, time) {
+        (None, None) => Ok(None),
+        (None, Some(_)) => Err(workspace_messages().pick_end_date.to_owned()),
+        (Some(date), time) => {
+            let date = parse_picked_date(Some(date))?;
+            let time = match time {
+                Some(time) => parse_picked_time(Some(time))?,
+                None => NaiveTime::from_hms(23, 59, 0),
+            };
+            Ok(Some(date.and_time(time)))
+        }
+    }
+}
+
+pub fn parse_max_score(value: Option<&str>) -> Result<i32, String> {
+    let out_of_range = || fill(workspace_messages().score_range, &[&MIN_SCORE, &MAX_SCORE]);
+    let score =
+        i32::from_str(value.map(str::trim).unwrap_or_default()).map_err(|_| out_of_range())?;
+    if score < MIN_SCORE || score > MAX_SCORE {
+        return Err(out_of_range());
+    }
+    Ok(score)
+}
+
+/// Weights are optional, an empty weight is `DEFAULT_WEIGHT`.
+pub fn parse_weight(value: Option<&str>) -> Result<i32, String> {
+    let out_of_range = || fill(workspace_messages().weight_range, &[&1, &MAX_WEIGHT]);
+    match value.map(str::trim) {
+        None | Some("") => Ok(DEFAULT_WEIGHT),
+        Some(weight) => match i32::from_str(weight) {
+            Ok(weight) if weight >= 1 && weight <= MAX_WEIGHT => Ok(weight),
+            _ => Err(out_of_range()),
+        },
+    }
+}
+
+/// A poll with a jury needs jury criteria and jury criteria need a jury.
+pub fn check_jury_tracks(has_jury: bool, criteria: &[DialogViewVariant]) -> Result<(), String> {
+    let text = workspace_messages();
+    let has_jury_criteria = criteria
+        .iter()
+        .any(|criterion| criterion.track == Track::Jury);
+    match (has_jury, has_jury_criteria) {
+        (true, false) => Err(text.jury_without_criteria.to_owned()),
+        (false, true) => Err(text.jury_criteria_without_jury.to_owned()),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::locale::workspace_messages;
+    use crate::ui_poll_view::{DialogViewVariant, Track};
+    use crate::validation::{
+        check, check_jury_tracks, parse_max_score, parse_picked_date, parse_picked_time,
+        parse_text, parse_weight, parse_window_end, ValidationErrors, DEFAULT_WEIGHT,
+    };
+    use chrono::{NaiveDate, NaiveTime};
+
+    #[test]
+    fn test_picked_dates() {
+        let date = NaiveDate::from_ymd(2019, 10, 1);
+        assert_eq!(parse_picked_date(Some("2019-10-01")), Ok(date));
+        assert!(parse_picked_date(None).is_err());
+        assert_eq!(
+            parse_picked_time(Some("09:30")),
+            Ok(NaiveTime::from_hms(9, 30, 0))
+        );
+        assert_eq!(parse_window_end(None, None), Ok(None));
+        assert!(parse_window_end(None, Some("09:30")).is_err());
+        assert_eq!(
+            parse_window_end(Some("2019-10-01"), None),
+            Ok(Some(date.and_hms(23, 59, 0)))
+        );
+        assert_eq!(
+            parse_window_end(Some("2019-10-01"), Some("18:00")),
+            Ok(Some(date.and_hms(18, 0, 0)))
+        );
+    }
+
+    #[test]
+    fn test_scores_and_errors() {
+        assert_eq!(parse_max_score(Some("10")), Ok(10));
+        assert!(parse_max_score(Some("0")).is_err());
+        assert!(parse_max_score(Some("101")).is_err());
+        assert!(parse_max_score(Some("ten")).is_err());
+        assert_eq!(parse_weight(None), Ok(DEFAULT_WEIGHT));
+        assert_eq!(parse_weight(Some(" 40 ")), Ok(40));
+        assert!(parse_weight(Some("0")).is_err());
+        assert!(parse_weight(Some("40%")).is_err());
+
+        let mut errors = ValidationErrors::new();
+        let title_id = Some("title_text_1".to_owned());
+        assert_eq!(check(&mut errors, &title_id, parse_text(Some("  "))), None);
+        assert_eq!(
+            check(&mut errors, &title_id, parse_text(Some(" Team "))),
+            Some("Team".to_owned())
+        );
+        assert_eq!(errors.len(), 1);
+        assert!(errors.contains_key("title_text_1"));
+    }
+
+    #[test]
+    fn test_jury_needs_jury_criteria() {
+        let criterion = |track| DialogViewVariant {
+            variant_text: "Idea".to_owned(),
+            max_score: 1..=10,
+            weight: 1,
+            track,
+        };
+        let audience = vec![criterion(Track::Audience)];
+        let both = vec![criterion(Track::Audience), criterion(Track::Jury)];
+        assert_eq!(check_jury_tracks(false, &audience), Ok(()));
+        assert_eq!(check_jury_tracks(true, &both), Ok(()));
+
+        let text = workspace_messages();
+        assert_eq!(
+            check_jury_tracks(true, &audience),
+            Err(text.jury_without_criteria.to_owned())
+        );
+        assert_eq!(
+            check_jury_tracks(false, &both),
+            Err(text.jury_criteria_without_jury.to_owned())
+        );
+    }
+}